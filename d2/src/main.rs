@@ -1,57 +1,48 @@
-use std::ops::{Add, Mul};
+mod symbolic;
 
-type Prog = Vec<usize>;
+use intcode::{read_prog, Machine, Prog, VecIO};
+use std::fs::read_to_string;
 
-fn read_prog(path: &str) -> Prog {
-    std::fs::read_to_string(path)
-        .unwrap()
-        .trim()
-        .split(',')
-        .map(|s| s.parse::<usize>().unwrap())
-        .collect()
-}
-
-fn run_op<F: FnOnce(usize, usize) -> usize>(pc: usize, prog: &mut Prog, f: F) {
-    let a = prog[pc + 1];
-    let b = prog[pc + 2];
-    let o = prog[pc + 3];
-    prog[o] = f(prog[a], prog[b]);
-}
+const TARGET: isize = 19690720;
 
-fn run_prog(mut prog: Prog, noun: usize, verb: usize) -> usize {
-    prog[1] = noun;
-    prog[2] = verb;
-    for pc in (0..).step_by(4) {
-        match prog[pc] {
-            1 => run_op(pc, &mut prog, Add::add),
-            2 => run_op(pc, &mut prog, Mul::mul),
-            99 => break,
-            _ => panic!("Unknown opcode"),
-        }
-    }
-    prog[0]
+fn run_prog(prog: &Prog, noun: isize, verb: isize) -> isize {
+    let mut machine = Machine::new(prog.to_owned());
+    machine.store(1, noun);
+    machine.store(2, verb);
+    machine.run(&mut VecIO::default()).unwrap();
+    machine.load(0)
 }
 
 fn part1(prog: &Prog) {
     // "... replace position 1 with the value 12 and replace position 2 with the value 2"
-    println!("part1 - answer {}", run_prog(prog.clone(), 12, 2));
+    println!("part1 - answer {}", run_prog(prog, 12, 2));
 }
 
-fn part2(prog: &Prog) {
+fn brute_force(prog: &Prog) -> Option<(isize, isize)> {
     for noun in 0..=99 {
         for verb in 0..=99 {
-            if run_prog(prog.clone(), noun, verb) == 19690720 {
-                let answer = 100 * noun + verb;
-                println!("part2 - noun {} verb {} answer {}", noun, verb, answer);
-                return;
+            if run_prog(prog, noun, verb) == TARGET {
+                return Some((noun, verb));
             }
         }
     }
-    panic!("No solution found");
+    None
+}
+
+fn part2(prog: &Prog) {
+    // Solve for the target directly if possible, saving up to 10,000 runs
+    let (noun, verb) = match symbolic::analyze(prog) {
+        Some(expr) => expr.solve(TARGET),
+        None => brute_force(prog),
+    }
+    .expect("No solution found");
+    assert_eq!(run_prog(prog, noun, verb), TARGET);
+    let answer = 100 * noun + verb;
+    println!("part2 - noun {} verb {} answer {}", noun, verb, answer);
 }
 
 fn main() {
-    let prog: Prog = read_prog("./input.txt");
+    let prog = read_prog(&read_to_string("./input.txt").unwrap());
     part1(&prog);
     part2(&prog);
 }
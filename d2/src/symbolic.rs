@@ -0,0 +1,222 @@
+// Runs a program with the noun and verb (positions 1 and 2) left as
+// unknowns, tracking every word as a linear expression of them. Only works
+// for programs that stick to add, mul and halt, never multiply two unknowns,
+// and never use an unknown as an opcode or address.
+//
+// Loading from an unknown address (ex. the `1,0,0,3` every day 2 program
+// starts with) gives a word that can't be expressed, which is fine as long as
+// it's overwritten before it's used. So does arithmetic that overflows.
+
+use intcode::Prog;
+use std::collections::{HashMap, HashSet};
+
+// constant + noun * noun_coefficient + verb * verb_coefficient
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Linear {
+    pub constant: isize,
+    pub noun: isize,
+    pub verb: isize,
+}
+
+impl Linear {
+    fn constant(constant: isize) -> Linear {
+        Linear {
+            constant,
+            noun: 0,
+            verb: 0,
+        }
+    }
+
+    fn as_constant(&self) -> Option<isize> {
+        if self.noun == 0 && self.verb == 0 {
+            Some(self.constant)
+        } else {
+            None
+        }
+    }
+
+    // `None` on overflow, like the rest of the arithmetic
+    fn add(&self, other: &Linear) -> Option<Linear> {
+        Some(Linear {
+            constant: self.constant.checked_add(other.constant)?,
+            noun: self.noun.checked_add(other.noun)?,
+            verb: self.verb.checked_add(other.verb)?,
+        })
+    }
+
+    fn mul(&self, other: &Linear) -> Option<Linear> {
+        let (k, e) = match (self.as_constant(), other.as_constant()) {
+            (Some(k), _) => (k, other),
+            (_, Some(k)) => (k, self),
+            _ => return None,
+        };
+        Some(Linear {
+            constant: k.checked_mul(e.constant)?,
+            noun: k.checked_mul(e.noun)?,
+            verb: k.checked_mul(e.verb)?,
+        })
+    }
+
+    // Some noun and verb, both 0-99, that evaluate to target
+    pub fn solve(&self, target: isize) -> Option<(isize, isize)> {
+        (0..=99).find_map(|noun| {
+            let rest = target
+                .checked_sub(self.constant)?
+                .checked_sub(self.noun.checked_mul(noun)?)?;
+            let verb = match self.verb {
+                0 if rest == 0 => 0,
+                0 => return None,
+                v if rest.checked_rem(v)? == 0 => rest.checked_div(v)?,
+                _ => return None,
+            };
+            if (0..=99).contains(&verb) {
+                Some((noun, verb))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+// Programs that run longer than this are assumed not to be day 2 programs
+const MAX_STEPS: usize = 10_000;
+
+// The expression left in position 0 when the program halts, or `None` if the
+// program can't be analyzed.
+pub fn analyze(prog: &Prog) -> Option<Linear> {
+    let mut memory: HashMap<usize, Linear> = prog
+        .iter()
+        .map(|(&address, &value)| (address, Linear::constant(value)))
+        .collect();
+    memory.insert(
+        1,
+        Linear {
+            constant: 0,
+            noun: 1,
+            verb: 0,
+        },
+    );
+    memory.insert(
+        2,
+        Linear {
+            constant: 0,
+            noun: 0,
+            verb: 1,
+        },
+    );
+    let load = |memory: &HashMap<usize, Linear>, address: usize| {
+        *memory.get(&address).unwrap_or(&Linear::constant(0))
+    };
+    let address = |memory: &HashMap<usize, Linear>, address: usize| {
+        let a = load(memory, address).as_constant()?;
+        if a < 0 {
+            None
+        } else {
+            Some(a as usize)
+        }
+    };
+    // Words that can't be expressed, by address
+    let mut unknown = HashSet::new();
+
+    let mut pc = 0;
+    for _ in 0..MAX_STEPS {
+        if unknown.contains(&pc) {
+            return None;
+        }
+        let op = load(&memory, pc).as_constant()?;
+        if op == 99 {
+            return if unknown.contains(&0) {
+                None
+            } else {
+                Some(load(&memory, 0))
+            };
+        }
+        if op != 1 && op != 2 {
+            // Other opcodes or parameter modes
+            return None;
+        }
+        let (a, b) = (address(&memory, pc + 1), address(&memory, pc + 2));
+        let dest = address(&memory, pc + 3)?;
+        if (1..=3).any(|i| unknown.contains(&(pc + i))) {
+            return None;
+        }
+        let value = match (a, b) {
+            (Some(a), Some(b)) if !unknown.contains(&a) && !unknown.contains(&b) => {
+                let (a, b) = (load(&memory, a), load(&memory, b));
+                if op == 1 {
+                    a.add(&b)
+                } else {
+                    a.mul(&b)
+                }
+            }
+            _ => None,
+        };
+        match value {
+            Some(value) => {
+                unknown.remove(&dest);
+                memory.insert(dest, value);
+            }
+            None => {
+                unknown.insert(dest);
+            }
+        }
+        pc += 4;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intcode::read_prog;
+    use std::fs::read_to_string;
+
+    #[test]
+    fn solves_input() {
+        let prog = read_prog(&read_to_string("./input.txt").unwrap());
+        let expr = analyze(&prog).unwrap();
+        assert_eq!(
+            expr.constant + 12 * expr.noun + 2 * expr.verb,
+            crate::run_prog(&prog, 12, 2)
+        );
+        let (noun, verb) = expr.solve(19690720).unwrap();
+        assert_eq!(crate::run_prog(&prog, noun, verb), 19690720);
+    }
+
+    #[test]
+    fn unknown_addresses() {
+        // Adds the words at the noun and verb, so the result can't be expressed
+        assert_eq!(analyze(&read_prog("1,0,0,0,99")), None);
+        // ... unless it's overwritten
+        assert_eq!(
+            analyze(&read_prog("1,0,0,0,1,1,2,0,99")),
+            Some(Linear {
+                constant: 0,
+                noun: 1,
+                verb: 1
+            })
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        // Squares 2^32
+        assert_eq!(analyze(&read_prog("2,5,5,0,99,4294967296")), None);
+        assert_eq!(
+            analyze(&read_prog("1,1,9,0,2,0,9,0,99,4611686018427387904")),
+            None
+        );
+        let huge = Linear {
+            constant: isize::MIN,
+            noun: 1,
+            verb: 1,
+        };
+        assert_eq!(huge.solve(1), None);
+        let negated = Linear {
+            constant: 0,
+            noun: 0,
+            verb: -1,
+        };
+        assert_eq!(negated.solve(isize::MIN), None);
+    }
+}
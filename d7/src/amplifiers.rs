@@ -0,0 +1,217 @@
+use intcode::{Machine, Prog, State};
+use std::collections::VecDeque;
+
+pub type PhaseSetting = isize;
+
+// Amplifier stages wired together by `(from, to)` links, each sending every
+// signal `from` outputs to `to`. A stage with several links into it takes one
+// signal from each and gets their sum, so fan-in sums.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Topology {
+    pub stages: usize,
+    pub links: Vec<(usize, usize)>,
+    // Gets the first signal (0) rather than one from its links
+    pub entry: usize,
+    // Its last output is the topology's signal
+    pub exit: usize,
+}
+
+impl Topology {
+    // Panics on no stages
+    pub fn chain(stages: usize) -> Topology {
+        assert!(stages >= 1, "A chain needs at least one stage");
+        Topology {
+            stages,
+            links: (1..stages).map(|i| (i - 1, i)).collect(),
+            entry: 0,
+            exit: stages - 1,
+        }
+    }
+
+    // A chain with the last stage feeding back into the first. Panics on no
+    // stages.
+    pub fn ring(stages: usize) -> Topology {
+        let mut ring = Topology::chain(stages);
+        ring.links.push((stages - 1, 0));
+        ring
+    }
+
+    // The first stage fanning out to `width` stages, summed by a last stage
+    pub fn fan(width: usize) -> Topology {
+        let exit = width + 1;
+        Topology {
+            stages: width + 2,
+            links: (1..=width).flat_map(|i| vec![(0, i), (i, exit)]).collect(),
+            entry: 0,
+            exit,
+        }
+    }
+
+    // Whether every stage it refers to is one of its stages
+    fn is_valid(&self) -> bool {
+        let stage = |s: usize| s < self.stages;
+        stage(self.entry)
+            && stage(self.exit)
+            && self
+                .links
+                .iter()
+                .all(|&(from, to)| stage(from) && stage(to))
+    }
+
+    // Runs every stage until they've all halted or are waiting on each other.
+    // `None` if the exit stage never outputs, a stage's program fails, or the
+    // topology refers to stages it doesn't have.
+    pub fn signal(&self, prog: &Prog, settings: &[PhaseSetting]) -> Option<isize> {
+        assert_eq!(settings.len(), self.stages, "One phase setting per stage");
+        if !self.is_valid() {
+            return None;
+        }
+        let mut amps: Vec<Machine> = settings
+            .iter()
+            .map(|&setting| {
+                let mut amp = Machine::new(prog.to_owned());
+                amp.push_input(setting);
+                amp
+            })
+            .collect();
+        amps[self.entry].push_input(0);
+        // Signals in flight on each link
+        let mut queues: Vec<VecDeque<isize>> = self.links.iter().map(|_| VecDeque::new()).collect();
+        let incoming: Vec<Vec<usize>> = (0..self.stages)
+            .map(|stage| {
+                (0..self.links.len())
+                    .filter(|&l| self.links[l].1 == stage)
+                    .collect()
+            })
+            .collect();
+        let mut halted = vec![false; self.stages];
+        let mut signal = None;
+        loop {
+            let mut progress = false;
+            for (stage, amp) in amps.iter_mut().enumerate() {
+                while !halted[stage] {
+                    match amp.resume().ok()? {
+                        State::NeedsInput => {
+                            let links = &incoming[stage];
+                            if links.is_empty() || links.iter().any(|&l| queues[l].is_empty()) {
+                                break;
+                            }
+                            amp.push_input(
+                                links.iter().map(|&l| queues[l].pop_front().unwrap()).sum(),
+                            );
+                        }
+                        State::Output(o) => {
+                            for (l, &(from, _)) in self.links.iter().enumerate() {
+                                if from == stage {
+                                    queues[l].push_back(o);
+                                }
+                            }
+                            if stage == self.exit {
+                                signal = Some(o);
+                            }
+                        }
+                        State::Halted => halted[stage] = true,
+                    }
+                    progress = true;
+                }
+            }
+            if !progress {
+                return signal;
+            }
+        }
+    }
+
+    // The settings (each stage set to a different one of `phases`) giving the
+    // highest signal, and that signal.
+    pub fn search(
+        &self,
+        prog: &Prog,
+        phases: &[PhaseSetting],
+    ) -> Option<(Vec<PhaseSetting>, isize)> {
+        let mut best: Option<(Vec<PhaseSetting>, isize)> = None;
+        let mut used = vec![false; phases.len()];
+        for_each_setting(
+            phases,
+            &mut used,
+            &mut vec![],
+            self.stages,
+            &mut |settings| {
+                if let Some(signal) = self.signal(prog, settings) {
+                    if best.as_ref().is_none_or(|&(_, max)| signal > max) {
+                        best = Some((settings.to_vec(), signal));
+                    }
+                }
+            },
+        );
+        best
+    }
+}
+
+// Phases are used up by position, so repeated values can each be used once.
+fn for_each_setting<F>(
+    phases: &[PhaseSetting],
+    used: &mut [bool],
+    settings: &mut Vec<PhaseSetting>,
+    stages: usize,
+    f: &mut F,
+) where
+    F: FnMut(&[PhaseSetting]),
+{
+    if settings.len() == stages {
+        return f(settings);
+    }
+    for (i, &phase) in phases.iter().enumerate() {
+        if !used[i] {
+            used[i] = true;
+            settings.push(phase);
+            for_each_setting(phases, used, settings, stages, f);
+            settings.pop();
+            used[i] = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intcode::read_prog;
+
+    #[test]
+    #[should_panic(expected = "at least one stage")]
+    fn empty_ring() {
+        Topology::ring(0);
+    }
+
+    #[test]
+    fn fan() {
+        // Outputs 10 * signal + phase
+        let prog = read_prog("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+        let fan = Topology::fan(2);
+        // 1, then 12 and 13, then 10 * (12 + 13) + 4
+        assert_eq!(fan.signal(&prog, &[1, 2, 3, 4]), Some(254));
+        assert_eq!(
+            fan.search(&prog, &[0, 1, 2, 3]),
+            Some((vec![3, 1, 2, 0], 630))
+        );
+        // Not enough phases to go around
+        assert_eq!(fan.search(&prog, &[0, 1, 2]), None);
+        // ... unless repeats count
+        assert_eq!(
+            fan.search(&prog, &[0, 1, 2, 2]).map(|(_, signal)| signal),
+            Some(430)
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let prog = read_prog("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+        let mut chain = Topology::chain(2);
+        chain.links.push((1, 2));
+        assert_eq!(chain.signal(&prog, &[0, 1]), None);
+        let mut chain = Topology::chain(2);
+        chain.exit = 2;
+        assert_eq!(chain.signal(&prog, &[0, 1]), None);
+        // A failing program
+        assert_eq!(Topology::chain(1).signal(&read_prog("3,0,42"), &[0]), None);
+    }
+}
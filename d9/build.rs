@@ -0,0 +1,12 @@
+// Transpiles the BOOST program (see `intcode::transpile`) into `boost.rs`
+use intcode::{read_prog, transpile::transpile};
+use std::env;
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=input.txt");
+    let prog = read_prog(&read_to_string("input.txt").unwrap());
+    let out = Path::new(&env::var_os("OUT_DIR").unwrap()).join("boost.rs");
+    write(out, transpile(&prog)).unwrap();
+}
@@ -0,0 +1,90 @@
+// Compares the decode cache against decoding every instruction, for both
+// memory backends, on the d9 BOOST and d13 arcade programs.
+//
+//     cargo bench --bench decode_cache
+
+use intcode::{read_prog, Machine, Memory, PagedMemory, Prog, State};
+use std::fs::read_to_string;
+use std::time::{Duration, Instant};
+
+const RUNS: u32 = 5;
+
+fn load(day: &str) -> Prog {
+    let path = format!("{}/../{}/input.txt", env!("CARGO_MANIFEST_DIR"), day);
+    read_prog(&read_to_string(path).unwrap())
+}
+
+// d9 part 2, running the BOOST program in sensor boost mode
+fn boost<M: Memory>(mut machine: Machine<M>) -> isize {
+    machine.push_input(2);
+    machine.expect_output().unwrap()
+}
+
+// d13 part 2, playing the arcade game to the end
+fn arcade<M: Memory>(mut machine: Machine<M>) -> isize {
+    machine.store(0, 2);
+    let (mut ball, mut paddle, mut score): (isize, isize, isize) = (0, 0, 0);
+    loop {
+        match machine.resume().unwrap() {
+            State::NeedsInput => machine.push_input((ball - paddle).signum()),
+            State::Output(x) => {
+                let _y = machine.expect_output().unwrap();
+                let tile = machine.expect_output().unwrap();
+                match (x, tile) {
+                    (-1, s) => score = s,
+                    (x, 3) => paddle = x,
+                    (x, 4) => ball = x,
+                    _ => (),
+                }
+            }
+            State::Halted => return score,
+        }
+    }
+}
+
+fn time<F: Fn() -> isize>(f: F) -> (Duration, isize) {
+    let result = f();
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        assert_eq!(f(), result);
+    }
+    (start.elapsed() / RUNS, result)
+}
+
+fn bench<F, G>(name: &str, prog: &Prog, run_sparse: F, run_paged: G)
+where
+    F: Fn(Machine<Prog>) -> isize,
+    G: Fn(Machine<PagedMemory>) -> isize,
+{
+    let sparse = |cached| {
+        let mut m = Machine::new(prog.clone());
+        m.set_decode_cache(cached);
+        m
+    };
+    let paged = |cached| {
+        let mut m = Machine::new(PagedMemory::from(prog));
+        m.set_decode_cache(cached);
+        m
+    };
+    let (baseline, result) = time(|| run_sparse(sparse(false)));
+    println!("{} (result {})", name, result);
+    let results = vec![
+        ("sparse", baseline),
+        ("sparse + decode cache", time(|| run_sparse(sparse(true))).0),
+        ("paged", time(|| run_paged(paged(false))).0),
+        ("paged + decode cache", time(|| run_paged(paged(true))).0),
+    ];
+    for (config, elapsed) in results {
+        println!(
+            "  {: <24} {: >10.3?} {: >6.2}x",
+            config,
+            elapsed,
+            baseline.as_secs_f64() / elapsed.as_secs_f64()
+        );
+    }
+}
+
+fn main() {
+    bench("d9 BOOST", &load("d9"), boost, boost);
+    bench("d13 arcade", &load("d13"), arcade, arcade);
+}
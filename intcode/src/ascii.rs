@@ -0,0 +1,90 @@
+// IO for programs that talk in ASCII: lines of text are fed as character
+// codes, each followed by a newline, and output characters are collected into
+// lines. Outputs that aren't ASCII (ex. a final answer too big for a
+// character) are kept separately.
+
+use crate::InputOutput;
+use std::collections::VecDeque;
+
+#[derive(Debug, Default, Clone)]
+pub struct AsciiIO {
+    inputs: VecDeque<isize>,
+    // Complete lines output so far, without their newlines
+    pub lines: Vec<String>,
+    // Output after the last newline
+    pub partial_line: String,
+    pub values: Vec<isize>,
+}
+
+impl AsciiIO {
+    pub fn new() -> AsciiIO {
+        AsciiIO::default()
+    }
+
+    pub fn send_line(&mut self, line: &str) {
+        self.inputs
+            .extend(line.bytes().chain(Some(b'\n')).map(isize::from));
+    }
+
+    // All text output so far, newlines included
+    pub fn text(&self) -> String {
+        let mut text: String = self.lines.iter().map(|l| format!("{}\n", l)).collect();
+        text += &self.partial_line;
+        text
+    }
+}
+
+impl InputOutput for AsciiIO {
+    fn input(&mut self) -> Option<isize> {
+        self.inputs.pop_front()
+    }
+
+    fn output(&mut self, o: isize) {
+        match o {
+            10 => self.lines.push(std::mem::take(&mut self.partial_line)),
+            0..=127 => self.partial_line.push(o as u8 as char),
+            _ => self.values.push(o),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::Machine;
+
+    #[test]
+    fn lines_and_values() {
+        // Echoes a line back in upper case, then outputs its length
+        let prog = assemble(
+            "
+            loop:   in [c]
+                    eq [c], #10, [t]
+                    jnz [t], #done
+                    lt [c], #97, [t]
+                    jnz [t], #echo
+                    add [c], #-32, [c]
+            echo:   out [c]
+                    add [n], #1, [n]
+                    jz #0, #loop
+            done:   out #10
+                    out #62
+                    mul [n], #1000, [n]
+                    out [n]
+                    halt
+            c:      data 0
+            t:      data 0
+            n:      data 0
+            ",
+        )
+        .unwrap();
+        let mut io = AsciiIO::new();
+        io.send_line("Hi there");
+        Machine::new(prog).run(&mut io).unwrap();
+        assert_eq!(io.lines, vec!["HI THERE"]);
+        assert_eq!(io.partial_line, ">");
+        assert_eq!(io.values, vec![8000]);
+        assert_eq!(io.text(), "HI THERE\n>");
+    }
+}
@@ -0,0 +1,329 @@
+// A small assembler for writing Intcode programs by hand.
+//
+//     ; Echo inputs until a 0 is read
+//     loop:   in [x]
+//             jz [x], #done
+//             out [x]
+//             jnz #1, #loop
+//     done:   halt
+//     x:      data 0
+//
+// Operands are `#v` (immediate), `[v]` (position) or `[rb+n]` (relative to
+// base), where `v` is a number, `label` or `label+n`/`label-n`. Operands
+// written to can also be `[#v]`, which is `[v]` with mode digit 1 rather than
+// 0. The directive `data v, v, ...` emits raw words.
+
+use crate::instruction::{ArgType, OPS};
+use crate::Prog;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(isize),
+    Label(String, isize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Position(Value),
+    Immediate(Value),
+    // A written operand in mode 1, which writes like position mode
+    ImmediateAddress(Value),
+    Relative(isize),
+}
+
+impl Operand {
+    fn mode(&self) -> isize {
+        match self {
+            Operand::Position(_) => 0,
+            Operand::Immediate(_) | Operand::ImmediateAddress(_) => 1,
+            Operand::Relative(_) => 2,
+        }
+    }
+}
+
+enum Item {
+    Instruction(isize, Vec<Operand>),
+    Data(Vec<Value>),
+}
+
+fn err<T>(line: usize, message: String) -> Result<T, AsmError> {
+    Err(AsmError { line, message })
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+fn parse_number(line: usize, s: &str) -> Result<isize, AsmError> {
+    match s.trim().parse() {
+        Ok(n) => Ok(n),
+        Err(_) => err(line, format!("invalid number `{}`", s.trim())),
+    }
+}
+
+fn parse_value(line: usize, s: &str) -> Result<Value, AsmError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return err(line, "missing value".to_string());
+    }
+    if !s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return parse_number(line, s).map(Value::Number);
+    }
+    let (name, offset) = match s.find(['+', '-']) {
+        Some(i) => (s[..i].trim(), parse_number(line, &s[i..].replace(' ', ""))?),
+        None => (s, 0),
+    };
+    if !is_ident(name) {
+        return err(line, format!("invalid label `{}`", name));
+    }
+    Ok(Value::Label(name.to_string(), offset))
+}
+
+fn parse_operand(line: usize, s: &str) -> Result<Operand, AsmError> {
+    let s = s.trim();
+    if let Some(v) = s.strip_prefix('#') {
+        return Ok(Operand::Immediate(parse_value(line, v)?));
+    }
+    if let Some(inner) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        let inner = inner.trim();
+        if let Some(v) = inner.strip_prefix('#') {
+            return Ok(Operand::ImmediateAddress(parse_value(line, v)?));
+        }
+        if inner == "rb" {
+            return Ok(Operand::Relative(0));
+        }
+        if let Some(offset) = inner.strip_prefix("rb") {
+            let offset = offset.trim();
+            if offset.starts_with('+') || offset.starts_with('-') {
+                return Ok(Operand::Relative(parse_number(
+                    line,
+                    &offset.replace(' ', ""),
+                )?));
+            }
+        }
+        return Ok(Operand::Position(parse_value(line, inner)?));
+    }
+    err(
+        line,
+        format!("invalid operand `{}` (expected #v, [v] or [rb+n])", s),
+    )
+}
+
+fn split_list(s: &str) -> Vec<&str> {
+    if s.trim().is_empty() {
+        vec![]
+    } else {
+        s.split(',').collect()
+    }
+}
+
+fn parse_item(line: usize, s: &str) -> Result<Item, AsmError> {
+    let (word, rest) = match s.find(char::is_whitespace) {
+        Some(i) => (&s[..i], &s[i..]),
+        None => (s, ""),
+    };
+    if word == "data" {
+        let values = split_list(rest)
+            .into_iter()
+            .map(|v| parse_value(line, v))
+            .collect::<Result<Vec<Value>, AsmError>>()?;
+        if values.is_empty() {
+            return err(line, "data needs at least one value".to_string());
+        }
+        return Ok(Item::Data(values));
+    }
+    let info = match OPS.iter().find(|info| info.mnemonic == word) {
+        Some(info) => info,
+        None => return err(line, format!("unknown mnemonic `{}`", word)),
+    };
+    let operands = split_list(rest)
+        .into_iter()
+        .map(|o| parse_operand(line, o))
+        .collect::<Result<Vec<Operand>, AsmError>>()?;
+    if operands.len() != info.args.len() {
+        return err(
+            line,
+            format!(
+                "`{}` takes {} operand(s), found {}",
+                word,
+                info.args.len(),
+                operands.len()
+            ),
+        );
+    }
+    for (i, (arg, operand)) in info.args.iter().zip(&operands).enumerate() {
+        if let (ArgType::Address, Operand::Immediate(_)) = (arg, operand) {
+            return err(
+                line,
+                format!(
+                    "operand {} of `{}` is written to, it can't be immediate",
+                    i + 1,
+                    word
+                ),
+            );
+        }
+        if let (ArgType::Value, Operand::ImmediateAddress(_)) = (arg, operand) {
+            return err(
+                line,
+                format!(
+                    "operand {} of `{}` isn't written to, it can't be `[#v]`",
+                    i + 1,
+                    word
+                ),
+            );
+        }
+    }
+    Ok(Item::Instruction(info.opcode, operands))
+}
+
+fn resolve(line: usize, labels: &HashMap<String, usize>, v: &Value) -> Result<isize, AsmError> {
+    match v {
+        Value::Number(n) => Ok(*n),
+        Value::Label(name, offset) => match labels.get(name) {
+            Some(&address) => Ok(address as isize + offset),
+            None => err(line, format!("undefined label `{}`", name)),
+        },
+    }
+}
+
+pub fn assemble_words(src: &str) -> Result<Vec<isize>, AsmError> {
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut items: Vec<(usize, Item)> = vec![];
+    let mut address = 0;
+    for (i, raw) in src.lines().enumerate() {
+        let line = i + 1;
+        let mut s = raw.split(';').next().unwrap().trim();
+        while let Some(colon) = s.find(':') {
+            let label = s[..colon].trim();
+            if !is_ident(label) {
+                return err(line, format!("invalid label `{}`", label));
+            }
+            if labels.insert(label.to_string(), address).is_some() {
+                return err(line, format!("duplicate label `{}`", label));
+            }
+            s = s[colon + 1..].trim();
+        }
+        if s.is_empty() {
+            continue;
+        }
+        let item = parse_item(line, s)?;
+        address += match &item {
+            Item::Instruction(_, operands) => 1 + operands.len(),
+            Item::Data(values) => values.len(),
+        };
+        items.push((line, item));
+    }
+
+    let mut words = Vec::with_capacity(address);
+    for (line, item) in items {
+        match item {
+            Item::Instruction(op, operands) => {
+                let modes = operands
+                    .iter()
+                    .enumerate()
+                    .map(|(i, o)| o.mode() * 10_isize.pow(i as u32 + 2))
+                    .sum::<isize>();
+                words.push(op + modes);
+                for o in operands {
+                    words.push(match o {
+                        Operand::Position(v)
+                        | Operand::Immediate(v)
+                        | Operand::ImmediateAddress(v) => resolve(line, &labels, &v)?,
+                        Operand::Relative(offset) => offset,
+                    });
+                }
+            }
+            Item::Data(values) => {
+                for v in values {
+                    words.push(resolve(line, &labels, &v)?);
+                }
+            }
+        }
+    }
+    Ok(words)
+}
+
+pub fn assemble(src: &str) -> Result<Prog, AsmError> {
+    Ok(assemble_words(src)?.into_iter().enumerate().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{read_prog, write_prog, Machine};
+
+    #[test]
+    fn encodes_modes() {
+        assert_eq!(
+            assemble_words("add [x], #5, [rb-2]\nhalt\nx: data 7"),
+            Ok(vec![21001, 5, 5, -2, 99, 7])
+        );
+        assert_eq!(
+            assemble_words("in [rb]\nout [rb+3]\narb #-1\njz #0, #end+1\nend: halt"),
+            Ok(vec![203, 0, 204, 3, 109, -1, 1106, 0, 10, 99])
+        );
+    }
+
+    #[test]
+    fn runs() {
+        let prog = assemble(
+            "
+            ; Echo inputs until a 0 is read
+            loop:   in [x]
+                    jz [x], #done
+                    out [x]
+                    jnz #1, #loop
+            done:   halt
+            x:      data 0
+            ",
+        )
+        .unwrap();
+        assert_eq!(read_prog(&write_prog(&prog)), prog);
+        assert_eq!(
+            Machine::run_once(&prog, vec![3, 2, 1, 0]),
+            Ok(vec![3, 2, 1])
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let e = |src| assemble_words(src).unwrap_err().to_string();
+        assert_eq!(e("halt\nfoo [1]"), "line 2: unknown mnemonic `foo`");
+        assert_eq!(
+            e("add [1], [2]"),
+            "line 1: `add` takes 3 operand(s), found 2"
+        );
+        assert_eq!(
+            e("\n\nin #1"),
+            "line 3: operand 1 of `in` is written to, it can't be immediate"
+        );
+        assert_eq!(e("out [nowhere]"), "line 1: undefined label `nowhere`");
+        assert_eq!(e("a: halt\na: halt"), "line 2: duplicate label `a`");
+        assert_eq!(
+            e("out 5"),
+            "line 1: invalid operand `5` (expected #v, [v] or [rb+n])"
+        );
+    }
+}
@@ -0,0 +1,11 @@
+use intcode::{cfg::control_flow_graph, read_prog};
+use std::env::args;
+use std::fs::read_to_string;
+
+fn main() {
+    let path = args().nth(1).expect("Usage: cfg <program file>");
+    print!(
+        "{}",
+        control_flow_graph(&read_prog(&read_to_string(path).unwrap())).to_dot()
+    );
+}
@@ -0,0 +1,26 @@
+use intcode::compiler::{compile, compile_prog};
+use intcode::write_prog;
+use std::env::args;
+use std::fs::read_to_string;
+
+fn main() {
+    let args: Vec<String> = args().skip(1).collect();
+    let (asm, path) = match args.as_slice() {
+        [flag, path] if flag == "--asm" => (true, path),
+        [path] => (false, path),
+        _ => panic!("Usage: compile [--asm] <source file>"),
+    };
+    let src = read_to_string(path).unwrap();
+    let out = if asm {
+        compile(&src).map(|asm| asm.trim_end().to_string())
+    } else {
+        compile_prog(&src).map(|prog| write_prog(&prog))
+    };
+    match out {
+        Ok(out) => println!("{}", out),
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use intcode::debugger::{Debugger, Stop};
+use intcode::repl::{address_range, parse, read_loop};
+use intcode::{read_prog, Machine, PagedMemory};
+use std::env::args;
+use std::fs::read_to_string;
+
+const HELP: &str = "\
+commands:
+  s [n]            step n instructions (default 1)
+  c                continue until breakpoint, watchpoint, input or halt
+  b <pc>           add breakpoint          db <pc>   delete breakpoint
+  w <addr>         add write watchpoint    dw <addr> delete watchpoint
+  x <addr> [len]   examine memory
+  set <addr> <v>   patch memory
+  base [v]         show or set the relative base
+  pc [v]           show or set the pc
+  in <v,v,...>     queue inputs
+  i                show the instruction at pc
+  info             list breakpoints, watchpoints and pending inputs
+  q                quit";
+
+fn show_instruction(d: &Debugger<PagedMemory>) {
+    match d.instruction() {
+        Some(inst) => println!("{:>6}: {}", d.machine.pc, inst),
+        None => println!("{:>6}: data {}", d.machine.pc, d.machine.load(d.machine.pc)),
+    }
+}
+
+fn command(d: &mut Debugger<PagedMemory>, line: &str) -> Result<bool, String> {
+    let mut words = line.split_whitespace();
+    let outputs_before = d.outputs.len();
+    let stop = match words.next() {
+        None => return Ok(true),
+        Some("s") => {
+            let n = words.next().map_or(Ok(1), |n| parse(Some(n)))?;
+            let mut stop = Stop::Stepped;
+            for _ in 0..n {
+                stop = d.step();
+                if stop != Stop::Stepped {
+                    break;
+                }
+            }
+            Some(stop)
+        }
+        Some("c") => Some(d.cont()),
+        Some("b") => {
+            d.add_breakpoint(parse(words.next())?);
+            None
+        }
+        Some("db") => {
+            if !d.remove_breakpoint(parse(words.next())?) {
+                println!("no such breakpoint");
+            }
+            None
+        }
+        Some("w") => {
+            d.add_watchpoint(parse(words.next())?);
+            None
+        }
+        Some("dw") => {
+            if !d.remove_watchpoint(parse(words.next())?) {
+                println!("no such watchpoint");
+            }
+            None
+        }
+        Some("x") => {
+            let address: usize = parse(words.next())?;
+            let len = words.next().map_or(Ok(1), |n| parse(Some(n)))?;
+            for a in address_range(address, len)? {
+                println!("[{}] {}", a, d.machine.load(a));
+            }
+            None
+        }
+        Some("set") => {
+            let address = parse(words.next())?;
+            d.machine.store(address, parse(words.next())?);
+            None
+        }
+        Some("base") => {
+            if let Some(v) = words.next() {
+                d.machine.base = parse(Some(v))?;
+            }
+            println!("base {}", d.machine.base);
+            None
+        }
+        Some("pc") => {
+            if let Some(v) = words.next() {
+                d.machine.pc = parse(Some(v))?;
+            }
+            show_instruction(d);
+            None
+        }
+        Some("in") => {
+            for v in words.collect::<String>().split(',') {
+                d.machine.push_input(parse(Some(v.trim()))?);
+            }
+            None
+        }
+        Some("i") => {
+            show_instruction(d);
+            None
+        }
+        Some("info") => {
+            let mut breakpoints: Vec<&usize> = d.breakpoints().iter().collect();
+            let mut watchpoints: Vec<&usize> = d.watchpoints().iter().collect();
+            breakpoints.sort();
+            watchpoints.sort();
+            println!("breakpoints {:?}", breakpoints);
+            println!("watchpoints {:?}", watchpoints);
+            println!(
+                "inputs {:?}",
+                d.machine.pending_inputs().collect::<Vec<_>>()
+            );
+            None
+        }
+        Some("q") => return Ok(false),
+        Some(_) => {
+            println!("{}", HELP);
+            None
+        }
+    };
+    for o in &d.outputs[outputs_before..] {
+        println!(" > {}", o);
+    }
+    if let Some(stop) = stop {
+        if stop != Stop::Stepped {
+            println!("{}", stop);
+        }
+        show_instruction(d);
+    }
+    Ok(true)
+}
+
+fn main() {
+    let path = args().nth(1).expect("Usage: debugger <program file>");
+    let prog = read_prog(&read_to_string(path).unwrap());
+    let mut d = Debugger::new(Machine::new(PagedMemory::from(&prog)));
+    show_instruction(&d);
+    read_loop("(icdb) ", |line| command(&mut d, line));
+}
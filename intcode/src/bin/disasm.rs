@@ -0,0 +1,8 @@
+use intcode::{disasm::listing, read_prog};
+use std::env::args;
+use std::fs::read_to_string;
+
+fn main() {
+    let path = args().nth(1).expect("Usage: disasm <program file>");
+    print!("{}", listing(&read_prog(&read_to_string(path).unwrap())));
+}
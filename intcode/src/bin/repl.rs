@@ -0,0 +1,15 @@
+use intcode::read_prog;
+use intcode::repl::{read_loop, Repl};
+use std::env::args;
+use std::fs::read_to_string;
+
+fn main() {
+    let path = args().nth(1).expect("Usage: repl <program file>");
+    let mut repl = Repl::new(read_prog(&read_to_string(path).unwrap()));
+    read_loop("intcode> ", |line| {
+        let mut out = String::new();
+        let more = repl.command(line, &mut out);
+        print!("{}", out);
+        more
+    });
+}
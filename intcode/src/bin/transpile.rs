@@ -0,0 +1,8 @@
+use intcode::{read_prog, transpile::transpile};
+use std::env::args;
+use std::fs::read_to_string;
+
+fn main() {
+    let path = args().nth(1).expect("Usage: transpile <program file>");
+    print!("{}", transpile(&read_prog(&read_to_string(path).unwrap())));
+}
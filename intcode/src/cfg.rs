@@ -0,0 +1,245 @@
+// Splits the code reachable from address 0 into basic blocks. Jump targets are
+// only known for immediate mode targets, any other jump is an edge to an
+// unknown block. A jump on an immediate condition (ex. `jnz #1, #start`) only
+// has the edge that can be taken.
+
+use crate::disasm::{decode, Instruction, Line};
+use crate::instruction::ParamMode;
+use crate::Prog;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    // Falls through to the next instruction
+    Next(usize),
+    Jump(usize),
+    Indirect,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    // Ends with `Line::Data` when the block runs into a word that doesn't
+    // decode to an instruction.
+    pub lines: Vec<Line>,
+    pub successors: Vec<Edge>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub blocks: BTreeMap<usize, Block>,
+}
+
+fn ends_block(inst: &Instruction) -> bool {
+    matches!(inst.opcode(), 5 | 6 | 99)
+}
+
+fn successors(inst: &Instruction) -> Vec<Edge> {
+    let next = Edge::Next(inst.address + inst.size());
+    match inst.opcode() {
+        5 | 6 => {
+            let (condition, target) = (&inst.params[0], &inst.params[1]);
+            let jump_if_zero = inst.opcode() == 6;
+            let (may_jump, may_fall) = match condition.mode {
+                ParamMode::Immediate => {
+                    let jumps = (condition.raw == 0) == jump_if_zero;
+                    (jumps, !jumps)
+                }
+                _ => (true, true),
+            };
+            let mut edges = vec![];
+            if may_jump {
+                edges.push(match target.mode {
+                    ParamMode::Immediate if target.raw >= 0 => Edge::Jump(target.raw as usize),
+                    // A negative target faults, so there's nowhere to go
+                    ParamMode::Immediate => return edges,
+                    _ => Edge::Indirect,
+                });
+            }
+            if may_fall {
+                edges.push(next);
+            }
+            edges
+        }
+        99 => vec![],
+        _ => vec![next],
+    }
+}
+
+pub fn control_flow_graph(prog: &Prog) -> Cfg {
+    control_flow_graph_from(prog, &[0])
+}
+
+// The code reachable from any of `entries`, for code that's only reached
+// through indirect jumps.
+pub fn control_flow_graph_from(prog: &Prog, entries: &[usize]) -> Cfg {
+    let load = |address: usize| *prog.get(&address).unwrap_or(&0);
+
+    // Find where every block starts by following each path through the code
+    let mut leaders: BTreeSet<usize> = entries.iter().cloned().collect();
+    let mut seen = HashSet::new();
+    let mut pending = entries.to_vec();
+    while let Some(mut pc) = pending.pop() {
+        while seen.insert(pc) {
+            let inst = match decode(load, pc) {
+                Some(inst) => inst,
+                None => break,
+            };
+            if ends_block(&inst) {
+                for edge in successors(&inst) {
+                    if let Edge::Next(to) | Edge::Jump(to) = edge {
+                        leaders.insert(to);
+                        pending.push(to);
+                    }
+                }
+                break;
+            }
+            pc += inst.size();
+        }
+    }
+
+    let blocks = leaders
+        .iter()
+        .map(|&start| {
+            let mut block = Block {
+                start,
+                lines: vec![],
+                successors: vec![],
+            };
+            let mut pc = start;
+            loop {
+                let inst = match decode(load, pc) {
+                    Some(inst) => inst,
+                    None => {
+                        let value = load(pc);
+                        block.lines.push(Line::Data { address: pc, value });
+                        break;
+                    }
+                };
+                let next = pc + inst.size();
+                let ends = ends_block(&inst);
+                if ends {
+                    block.successors = successors(&inst);
+                } else if leaders.contains(&next) {
+                    block.successors = vec![Edge::Next(next)];
+                }
+                block.lines.push(Line::Instruction(inst));
+                if ends || !block.successors.is_empty() {
+                    break;
+                }
+                pc = next;
+            }
+            (start, block)
+        })
+        .collect();
+    Cfg { blocks }
+}
+
+impl Cfg {
+    // Graphviz source, each block labelled with its disassembly
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        let mut indirect = false;
+        for block in self.blocks.values() {
+            let label: String = block
+                .lines
+                .iter()
+                .map(|line| format!("{}: {}\\l", line.address(), line.text()))
+                .collect();
+            dot += &format!("    b{} [label=\"{}\"];\n", block.start, label);
+            for edge in &block.successors {
+                dot += &match edge {
+                    Edge::Next(to) => format!("    b{} -> b{};\n", block.start, to),
+                    Edge::Jump(to) => format!("    b{} -> b{} [label=jump];\n", block.start, to),
+                    Edge::Indirect => {
+                        indirect = true;
+                        format!("    b{} -> unknown [style=dashed];\n", block.start)
+                    }
+                };
+            }
+        }
+        if indirect {
+            dot += "    unknown [label=\"?\", shape=ellipse];\n";
+        }
+        dot + "}\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    #[test]
+    fn blocks_and_edges() {
+        let prog = assemble(
+            "
+                    in [n]
+            loop:   out [n]
+                    add [n], #-1, [n]
+                    jnz [n], #loop
+                    jz #0, #done
+                    data 12345
+            done:   jnz #1, [ret]
+            ret:    data 99
+            n:      data 0
+            ",
+        )
+        .unwrap();
+        let cfg = control_flow_graph(&prog);
+        let blocks: Vec<(usize, Vec<Edge>)> = cfg
+            .blocks
+            .values()
+            .map(|b| (b.start, b.successors.clone()))
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                (0, vec![Edge::Next(2)]),
+                (2, vec![Edge::Jump(2), Edge::Next(11)]),
+                (11, vec![Edge::Jump(15)]),
+                (15, vec![Edge::Indirect]),
+            ]
+        );
+        assert_eq!(
+            cfg.blocks[&2]
+                .lines
+                .iter()
+                .map(Line::text)
+                .collect::<Vec<String>>(),
+            vec!["out [19]", "add [19], #-1, [19]", "jnz [19], #2"]
+        );
+    }
+
+    #[test]
+    fn ends_on_data() {
+        let cfg = control_flow_graph(&assemble("out #1\ndata 42").unwrap());
+        assert_eq!(cfg.blocks.len(), 1);
+        assert_eq!(
+            cfg.blocks[&0].lines[1],
+            Line::Data {
+                address: 2,
+                value: 42
+            }
+        );
+        assert!(cfg.blocks[&0].successors.is_empty());
+    }
+
+    #[test]
+    fn dot() {
+        let prog = assemble("start: in [rb]\njz [rb], #start\njnz #1, [rb]").unwrap();
+        assert_eq!(
+            control_flow_graph(&prog).to_dot(),
+            "digraph cfg {
+    node [shape=box, fontname=monospace];
+    b0 [label=\"0: in [rb]\\l2: jz [rb], #0\\l\"];
+    b0 -> b0 [label=jump];
+    b0 -> b5;
+    b5 [label=\"5: jnz #1, [rb]\\l\"];
+    b5 -> unknown [style=dashed];
+    unknown [label=\"?\", shape=ellipse];
+}
+"
+        );
+    }
+}
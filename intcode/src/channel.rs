@@ -0,0 +1,172 @@
+// Runs machines on their own threads, connected by channels. A machine
+// blocks when it needs input until one is sent to it, and fails with
+// `IntcodeError::NoInput` once every sender to it is gone, or once it's
+// stopped.
+
+use crate::{InputOutput, IntcodeError, Machine, Memory};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{spawn, JoinHandle};
+use std::time::Duration;
+
+// How often a machine waiting for input checks whether it's been stopped
+const STOP_POLL: Duration = Duration::from_millis(10);
+
+pub struct ChannelIO {
+    pub input: Receiver<isize>,
+    pub outputs: Vec<Sender<isize>>,
+    // Once set, the machine gets no more input
+    pub stop: Arc<AtomicBool>,
+}
+
+impl InputOutput for ChannelIO {
+    fn input(&mut self) -> Option<isize> {
+        while !self.stop.load(Ordering::Relaxed) {
+            match self.input.recv_timeout(STOP_POLL) {
+                Ok(input) => return Some(input),
+                Err(RecvTimeoutError::Disconnected) => return None,
+                Err(RecvTimeoutError::Timeout) => (),
+            }
+        }
+        None
+    }
+    fn output(&mut self, o: isize) {
+        for output in &self.outputs {
+            // Whoever was listening may have halted already
+            let _ = output.send(o);
+        }
+    }
+}
+
+pub struct Network {
+    // Send into the input of each machine
+    pub inputs: Vec<Sender<isize>>,
+    // Everything each machine outputs, regardless of where it's linked to
+    pub outputs: Vec<Receiver<isize>>,
+    handles: Vec<JoinHandle<Result<(), IntcodeError>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Network {
+    // Each `(from, to)` link sends every output of machine `from` to the input
+    // of machine `to`, so any topology (chains, rings, fan-out, fan-in) can be
+    // wired up.
+    pub fn spawn<M>(machines: Vec<Machine<M>>, links: &[(usize, usize)]) -> Network
+    where
+        M: Memory + Send + 'static,
+    {
+        let (inputs, input_receivers): (Vec<Sender<isize>>, Vec<Receiver<isize>>) =
+            machines.iter().map(|_| channel()).unzip();
+        let (taps, outputs): (Vec<Sender<isize>>, Vec<Receiver<isize>>) =
+            machines.iter().map(|_| channel()).unzip();
+        let stop = Arc::new(AtomicBool::new(false));
+        let handles = machines
+            .into_iter()
+            .zip(input_receivers)
+            .zip(taps)
+            .enumerate()
+            .map(|(i, ((mut machine, input), tap))| {
+                let mut outputs = vec![tap];
+                outputs.extend(
+                    links
+                        .iter()
+                        .filter(|&&(from, _)| from == i)
+                        .map(|&(_, to)| inputs[to].clone()),
+                );
+                let stop = stop.clone();
+                spawn(move || {
+                    machine.run(&mut ChannelIO {
+                        input,
+                        outputs,
+                        stop,
+                    })
+                })
+            })
+            .collect();
+        Network {
+            inputs,
+            outputs,
+            handles,
+            stop,
+        }
+    }
+
+    // Waits for every machine to stop, returning each machine's result.
+    // Machines linked in a cycle can keep each other waiting for input
+    // forever, and then this never returns: use `stop` for those.
+    pub fn join(self) -> Vec<Result<(), IntcodeError>> {
+        drop(self.inputs);
+        self.handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Machine thread panicked"))
+            .collect()
+    }
+
+    // Stops every machine the next time it waits for input (failing with
+    // `IntcodeError::NoInput`), then joins them. Input already sent but not
+    // yet read is dropped. A machine that never asks for input again can't be
+    // stopped.
+    pub fn stop(self) -> Vec<Result<(), IntcodeError>> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    #[test]
+    fn chain_and_fan_in() {
+        // Doubles each input: 0 -> 1 and 0 -> 2, then 1 -> 3 and 2 -> 3
+        let doubler = assemble(
+            "
+            loop:   in [x]
+                    mul [x], #2, [x]
+                    out [x]
+                    jnz #1, #loop
+            x:      data 0
+            ",
+        )
+        .unwrap();
+        let machines = (0..4).map(|_| Machine::new(doubler.clone())).collect();
+        let network = Network::spawn(machines, &[(0, 1), (0, 2), (1, 3), (2, 3)]);
+        network.inputs[0].send(1).unwrap();
+        network.inputs[0].send(5).unwrap();
+        // Fan-in interleaves outputs from 1 and 2 in whatever order they run
+        let mut third: Vec<isize> = network.outputs[3].iter().take(4).collect();
+        third.sort();
+        assert_eq!(third, vec![8, 8, 40, 40]);
+        let results = network.join();
+        assert!(results
+            .iter()
+            .all(|r| matches!(r, Err(IntcodeError::NoInput { .. }))));
+    }
+
+    #[test]
+    fn stopping_a_ring() {
+        let echo = assemble(
+            "
+            loop:   in [x]
+                    out [x]
+                    jnz #1, #loop
+            x:      data 0
+            ",
+        )
+        .unwrap();
+        let machines = (0..2).map(|_| Machine::new(echo.clone())).collect();
+        let network = Network::spawn(machines, &[(0, 1), (1, 0)]);
+        network.inputs[0].send(7).unwrap();
+        // Goes round and round, so `join` would never return
+        assert_eq!(
+            network.outputs[1].iter().take(3).collect::<Vec<_>>(),
+            vec![7, 7, 7]
+        );
+        let results = network.stop();
+        assert!(results
+            .iter()
+            .all(|r| matches!(r, Err(IntcodeError::NoInput { .. }))));
+    }
+}
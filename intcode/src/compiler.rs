@@ -0,0 +1,797 @@
+// Compiles a small imperative language to assembler source (see `asm`).
+//
+//     // Outputs the first n Fibonacci numbers
+//     let calls = 0;
+//
+//     fn fib(n) {
+//         calls = calls + 1;
+//         if n < 2 {
+//             return n;
+//         }
+//         return fib(n - 1) + fib(n - 2);
+//     }
+//
+//     fn main() {
+//         let n = read();
+//         let i = 0;
+//         while i < n {
+//             write(fib(i));
+//             i = i + 1;
+//         }
+//     }
+//
+// Values are Intcode words. Expressions have `+ - *`, comparisons
+// `< > <= >= == !=` (1 for true, 0 for false), unary `-` and `!`, and calls.
+// `read()` and `write(v)` are the in and out instructions. Globals are
+// declared at the top level with a constant initial value. Functions return 0
+// unless they return something else, and the program halts when `main`
+// returns.
+//
+// The relative base points at the current function's frame on a stack after
+// the program:
+//
+//     [rb]             return address
+//     [rb+1]...        params, then every local declared in the function
+//     after that       temporaries for evaluating expressions
+//
+// A call evaluates the args into the next free temporaries, so they become the
+// callee's params, and moves the base up to them. Return values are passed
+// in a fixed word.
+
+use crate::asm::assemble;
+use crate::Prog;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub struct CompileError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+fn err<T>(line: usize, message: String) -> Result<T, CompileError> {
+    Err(CompileError { line, message })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(isize),
+    Ident(String),
+    Punct(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Ident(s) => write!(f, "{}", s),
+            Token::Punct(p) => write!(f, "{}", p),
+        }
+    }
+}
+
+// Longest first, so `<=` isn't read as `<` then `=`
+const PUNCTS: [&str; 17] = [
+    "<=", ">=", "==", "!=", "(", ")", "{", "}", ",", ";", "=", "+", "-", "*", "<", ">", "!",
+];
+
+fn tokenize(src: &str) -> Result<Vec<(usize, Token)>, CompileError> {
+    let mut tokens = vec![];
+    for (i, text) in src.lines().enumerate() {
+        let line = i + 1;
+        let mut s = text.split("//").next().unwrap();
+        loop {
+            s = s.trim_start();
+            let c = match s.chars().next() {
+                Some(c) => c,
+                None => break,
+            };
+            let len = if c.is_ascii_digit() {
+                let len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+                match s[..len].parse() {
+                    Ok(n) => tokens.push((line, Token::Number(n))),
+                    Err(_) => return err(line, format!("number `{}` is too big", &s[..len])),
+                }
+                len
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let len = s
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(s.len());
+                tokens.push((line, Token::Ident(s[..len].to_string())));
+                len
+            } else {
+                match PUNCTS.iter().find(|p| s.starts_with(*p)) {
+                    Some(p) => {
+                        tokens.push((line, Token::Punct(p)));
+                        p.len()
+                    }
+                    None => return err(line, format!("unexpected `{}`", c)),
+                }
+            };
+            s = &s[len..];
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(isize),
+    Var(String),
+    Call(String, Vec<Expr>),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn has_call(&self) -> bool {
+        match self {
+            Expr::Number(_) | Expr::Var(_) => false,
+            Expr::Call(..) => true,
+            Expr::Neg(e) | Expr::Not(e) => e.has_call(),
+            Expr::Binary(_, a, b) => a.has_call() || b.has_call(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Stmt {
+    Let(String, Expr),
+    Assign(String, Expr),
+    If(Expr, Vec<(usize, Stmt)>, Vec<(usize, Stmt)>),
+    While(Expr, Vec<(usize, Stmt)>),
+    Return(Option<Expr>),
+    Expr(Expr),
+}
+
+struct Function {
+    line: usize,
+    name: String,
+    params: Vec<String>,
+    body: Vec<(usize, Stmt)>,
+}
+
+struct Program {
+    globals: Vec<(String, isize)>,
+    functions: Vec<Function>,
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        match self.tokens.get(self.pos).or_else(|| self.tokens.last()) {
+            Some((line, _)) => *line,
+            None => 1,
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn is(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(p)) if *p == punct)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(s)) if s == keyword)
+    }
+
+    fn advance(&mut self) -> Result<Token, CompileError> {
+        match self.tokens.get(self.pos) {
+            Some((_, t)) => {
+                self.pos += 1;
+                Ok(t.clone())
+            }
+            None => err(self.line(), "unexpected end of program".to_string()),
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), CompileError> {
+        let line = self.line();
+        match self.advance()? {
+            Token::Punct(p) if p == punct => Ok(()),
+            t => err(line, format!("expected `{}`, found `{}`", punct, t)),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, CompileError> {
+        let line = self.line();
+        match self.advance()? {
+            Token::Ident(s) if !KEYWORDS.contains(&s.as_str()) => Ok(s),
+            t => err(line, format!("expected a name, found `{}`", t)),
+        }
+    }
+
+    fn program(&mut self) -> Result<Program, CompileError> {
+        let mut globals = vec![];
+        let mut functions = vec![];
+        while self.peek().is_some() {
+            let line = self.line();
+            if self.is_keyword("let") {
+                self.pos += 1;
+                let name = self.ident()?;
+                self.expect("=")?;
+                let negative = self.is("-");
+                if negative {
+                    self.pos += 1;
+                }
+                let value = match self.advance()? {
+                    Token::Number(n) if negative => -n,
+                    Token::Number(n) => n,
+                    t => return err(line, format!("expected a number, found `{}`", t)),
+                };
+                self.expect(";")?;
+                globals.push((name, value));
+            } else if self.is_keyword("fn") {
+                self.pos += 1;
+                let name = self.ident()?;
+                self.expect("(")?;
+                let mut params = vec![];
+                while !self.is(")") {
+                    if !params.is_empty() {
+                        self.expect(",")?;
+                    }
+                    params.push(self.ident()?);
+                }
+                self.expect(")")?;
+                functions.push(Function {
+                    line,
+                    name,
+                    params,
+                    body: self.block()?,
+                });
+            } else {
+                return err(line, "expected `fn` or `let`".to_string());
+            }
+        }
+        Ok(Program { globals, functions })
+    }
+
+    fn block(&mut self) -> Result<Vec<(usize, Stmt)>, CompileError> {
+        self.expect("{")?;
+        let mut stmts = vec![];
+        while !self.is("}") {
+            stmts.push((self.line(), self.stmt()?));
+        }
+        self.expect("}")?;
+        Ok(stmts)
+    }
+
+    fn stmt(&mut self) -> Result<Stmt, CompileError> {
+        if self.is_keyword("let") {
+            self.pos += 1;
+            let name = self.ident()?;
+            self.expect("=")?;
+            let e = self.expr()?;
+            self.expect(";")?;
+            return Ok(Stmt::Let(name, e));
+        }
+        if self.is_keyword("if") {
+            self.pos += 1;
+            let condition = self.expr()?;
+            let then = self.block()?;
+            let mut otherwise = vec![];
+            if self.is_keyword("else") {
+                self.pos += 1;
+                otherwise = if self.is_keyword("if") {
+                    vec![(self.line(), self.stmt()?)]
+                } else {
+                    self.block()?
+                };
+            }
+            return Ok(Stmt::If(condition, then, otherwise));
+        }
+        if self.is_keyword("while") {
+            self.pos += 1;
+            let condition = self.expr()?;
+            return Ok(Stmt::While(condition, self.block()?));
+        }
+        if self.is_keyword("return") {
+            self.pos += 1;
+            let e = if self.is(";") {
+                None
+            } else {
+                Some(self.expr()?)
+            };
+            self.expect(";")?;
+            return Ok(Stmt::Return(e));
+        }
+        let is_assign = matches!(self.tokens.get(self.pos + 1), Some((_, Token::Punct("="))));
+        let stmt = if is_assign {
+            let name = self.ident()?;
+            self.expect("=")?;
+            Stmt::Assign(name, self.expr()?)
+        } else {
+            Stmt::Expr(self.expr()?)
+        };
+        self.expect(";")?;
+        Ok(stmt)
+    }
+
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        let ops = [
+            ("<", BinOp::Lt),
+            (">", BinOp::Gt),
+            ("<=", BinOp::Le),
+            (">=", BinOp::Ge),
+            ("==", BinOp::Eq),
+            ("!=", BinOp::Ne),
+        ];
+        self.binary(&ops, Parser::additive)
+    }
+
+    fn additive(&mut self) -> Result<Expr, CompileError> {
+        self.binary(&[("+", BinOp::Add), ("-", BinOp::Sub)], Parser::term)
+    }
+
+    fn term(&mut self) -> Result<Expr, CompileError> {
+        self.binary(&[("*", BinOp::Mul)], Parser::unary)
+    }
+
+    // Left associative
+    fn binary(
+        &mut self,
+        ops: &[(&str, BinOp)],
+        operand: fn(&mut Parser) -> Result<Expr, CompileError>,
+    ) -> Result<Expr, CompileError> {
+        let mut e = operand(self)?;
+        while let Some(&(_, op)) = ops.iter().find(|(p, _)| self.is(p)) {
+            self.pos += 1;
+            e = Expr::Binary(op, Box::new(e), Box::new(operand(self)?));
+        }
+        Ok(e)
+    }
+
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if self.is("-") {
+            self.pos += 1;
+            return Ok(match self.unary()? {
+                Expr::Number(n) => Expr::Number(-n),
+                e => Expr::Neg(Box::new(e)),
+            });
+        }
+        if self.is("!") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let line = self.line();
+        match self.advance()? {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Punct("(") => {
+                let e = self.expr()?;
+                self.expect(")")?;
+                Ok(e)
+            }
+            Token::Ident(name) if !KEYWORDS.contains(&name.as_str()) => {
+                if !self.is("(") {
+                    return Ok(Expr::Var(name));
+                }
+                self.pos += 1;
+                let mut args = vec![];
+                while !self.is(")") {
+                    if !args.is_empty() {
+                        self.expect(",")?;
+                    }
+                    args.push(self.expr()?);
+                }
+                self.expect(")")?;
+                Ok(Expr::Call(name, args))
+            }
+            t => err(line, format!("expected an expression, found `{}`", t)),
+        }
+    }
+}
+
+const KEYWORDS: [&str; 6] = ["fn", "let", "if", "else", "while", "return"];
+
+// Builtins, by name and arg count
+const BUILTINS: [(&str, usize); 2] = [("read", 0), ("write", 1)];
+
+fn count_lets(stmts: &[(usize, Stmt)]) -> usize {
+    stmts
+        .iter()
+        .map(|(_, stmt)| match stmt {
+            Stmt::Let(..) => 1,
+            Stmt::If(_, then, otherwise) => count_lets(then) + count_lets(otherwise),
+            Stmt::While(_, body) => count_lets(body),
+            _ => 0,
+        })
+        .sum()
+}
+
+// Where a function's variables live, relative to the base
+struct Frame {
+    scopes: Vec<Vec<(String, isize)>>,
+    next_local: isize,
+    // The first temporary
+    temps: isize,
+}
+
+struct Codegen<'a> {
+    lines: Vec<String>,
+    next_label: usize,
+    globals: &'a HashSet<String>,
+    functions: &'a HashMap<String, usize>,
+}
+
+impl Codegen<'_> {
+    fn emit(&mut self, line: String) {
+        self.lines.push(format!("        {}", line));
+    }
+
+    fn label(&mut self) -> String {
+        self.next_label += 1;
+        format!("L{}", self.next_label)
+    }
+
+    fn place(&mut self, label: &str) {
+        self.lines.push(format!("{}:", label));
+    }
+
+    fn var(&self, frame: &Frame, line: usize, name: &str) -> Result<String, CompileError> {
+        for scope in frame.scopes.iter().rev() {
+            if let Some((_, slot)) = scope.iter().rev().find(|(n, _)| n == name) {
+                return Ok(format!("[rb+{}]", slot));
+            }
+        }
+        if self.globals.contains(name) {
+            return Ok(format!("[g_{}]", name));
+        }
+        err(line, format!("undefined variable `{}`", name))
+    }
+
+    // Evaluates `e`, returning an operand for its value. Anything that has to
+    // be computed ends up in temporary `slot`, and temporaries after it may be
+    // overwritten.
+    fn expr(
+        &mut self,
+        frame: &Frame,
+        line: usize,
+        e: &Expr,
+        slot: isize,
+    ) -> Result<String, CompileError> {
+        let dest = format!("[rb+{}]", slot);
+        match e {
+            Expr::Number(n) => return Ok(format!("#{}", n)),
+            Expr::Var(name) => return self.var(frame, line, name),
+            Expr::Neg(a) => {
+                let a = self.expr(frame, line, a, slot)?;
+                self.emit(format!("mul {}, #-1, {}", a, dest));
+            }
+            Expr::Not(a) => {
+                let a = self.expr(frame, line, a, slot)?;
+                self.emit(format!("eq {}, #0, {}", a, dest));
+            }
+            Expr::Binary(op, a, b) => {
+                let mut a = self.expr(frame, line, a, slot)?;
+                // A call could change a global `a` reads
+                if b.has_call() && a != dest {
+                    self.emit(format!("add {}, #0, {}", a, dest));
+                    a = dest.clone();
+                }
+                let b = self.expr(frame, line, b, slot + 1)?;
+                match op {
+                    BinOp::Add => self.emit(format!("add {}, {}, {}", a, b, dest)),
+                    BinOp::Sub => {
+                        let negated = format!("[rb+{}]", slot + 1);
+                        self.emit(format!("mul {}, #-1, {}", b, negated));
+                        self.emit(format!("add {}, {}, {}", a, negated, dest));
+                    }
+                    BinOp::Mul => self.emit(format!("mul {}, {}, {}", a, b, dest)),
+                    BinOp::Lt | BinOp::Ge => self.emit(format!("lt {}, {}, {}", a, b, dest)),
+                    BinOp::Gt | BinOp::Le => self.emit(format!("lt {}, {}, {}", b, a, dest)),
+                    BinOp::Eq | BinOp::Ne => self.emit(format!("eq {}, {}, {}", a, b, dest)),
+                }
+                if let BinOp::Ge | BinOp::Le | BinOp::Ne = op {
+                    self.emit(format!("eq {}, #0, {}", dest, dest));
+                }
+            }
+            Expr::Call(name, args) => {
+                let arity = BUILTINS
+                    .iter()
+                    .find(|(builtin, _)| builtin == name)
+                    .map(|&(_, arity)| arity)
+                    .or_else(|| self.functions.get(name).cloned());
+                match arity {
+                    Some(arity) if arity == args.len() => (),
+                    Some(arity) => {
+                        return err(
+                            line,
+                            format!("`{}` takes {} arg(s), found {}", name, arity, args.len()),
+                        )
+                    }
+                    None => return err(line, format!("undefined function `{}`", name)),
+                }
+                match name.as_str() {
+                    "read" => self.emit(format!("in {}", dest)),
+                    "write" => {
+                        let v = self.expr(frame, line, &args[0], slot)?;
+                        self.emit(format!("out {}", v));
+                        return Ok("#0".to_string());
+                    }
+                    _ => {
+                        for (i, arg) in args.iter().enumerate() {
+                            let param_slot = slot + 1 + i as isize;
+                            let param = format!("[rb+{}]", param_slot);
+                            let v = self.expr(frame, line, arg, param_slot)?;
+                            if v != param {
+                                self.emit(format!("add {}, #0, {}", v, param));
+                            }
+                        }
+                        let back = self.label();
+                        self.emit(format!("add #{}, #0, {}", back, dest));
+                        self.emit(format!("arb #{}", slot));
+                        self.emit(format!("jz #0, #fn_{}", name));
+                        self.place(&back);
+                        self.emit(format!("arb #-{}", slot));
+                        self.emit(format!("add [ret], #0, {}", dest));
+                    }
+                }
+            }
+        }
+        Ok(dest)
+    }
+
+    fn stmts(&mut self, frame: &mut Frame, stmts: &[(usize, Stmt)]) -> Result<(), CompileError> {
+        frame.scopes.push(vec![]);
+        for (line, stmt) in stmts {
+            self.stmt(frame, *line, stmt)?;
+        }
+        frame.scopes.pop();
+        Ok(())
+    }
+
+    fn stmt(&mut self, frame: &mut Frame, line: usize, stmt: &Stmt) -> Result<(), CompileError> {
+        match stmt {
+            Stmt::Let(name, e) => {
+                let v = self.expr(frame, line, e, frame.temps)?;
+                let slot = frame.next_local;
+                frame.next_local += 1;
+                frame
+                    .scopes
+                    .last_mut()
+                    .unwrap()
+                    .push((name.to_string(), slot));
+                self.emit(format!("add {}, #0, [rb+{}]", v, slot));
+            }
+            Stmt::Assign(name, e) => {
+                let v = self.expr(frame, line, e, frame.temps)?;
+                let dest = self.var(frame, line, name)?;
+                self.emit(format!("add {}, #0, {}", v, dest));
+            }
+            Stmt::If(condition, then, otherwise) => {
+                let v = self.expr(frame, line, condition, frame.temps)?;
+                let (else_label, end_label) = (self.label(), self.label());
+                self.emit(format!("jz {}, #{}", v, else_label));
+                self.stmts(frame, then)?;
+                if !otherwise.is_empty() {
+                    self.emit(format!("jz #0, #{}", end_label));
+                }
+                self.place(&else_label);
+                self.stmts(frame, otherwise)?;
+                self.place(&end_label);
+            }
+            Stmt::While(condition, body) => {
+                let (top_label, end_label) = (self.label(), self.label());
+                self.place(&top_label);
+                let v = self.expr(frame, line, condition, frame.temps)?;
+                self.emit(format!("jz {}, #{}", v, end_label));
+                self.stmts(frame, body)?;
+                self.emit(format!("jz #0, #{}", top_label));
+                self.place(&end_label);
+            }
+            Stmt::Return(e) => {
+                let v = match e {
+                    Some(e) => self.expr(frame, line, e, frame.temps)?,
+                    None => "#0".to_string(),
+                };
+                self.emit(format!("add {}, #0, [ret]", v));
+                self.emit("jz #0, [rb]".to_string());
+            }
+            Stmt::Expr(e) => {
+                self.expr(frame, line, e, frame.temps)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, f: &Function) -> Result<(), CompileError> {
+        let params = f.params.len() as isize;
+        let mut frame = Frame {
+            scopes: vec![f
+                .params
+                .iter()
+                .enumerate()
+                .map(|(i, p)| (p.to_string(), 1 + i as isize))
+                .collect()],
+            next_local: 1 + params,
+            temps: 1 + params + count_lets(&f.body) as isize,
+        };
+        self.lines.push(String::new());
+        self.lines
+            .push(format!("; fn {}({})", f.name, f.params.join(", ")));
+        self.place(&format!("fn_{}", f.name));
+        self.stmts(&mut frame, &f.body)?;
+        self.stmt(&mut frame, f.line, &Stmt::Return(None))
+    }
+}
+
+// Assembler source for the program
+pub fn compile(src: &str) -> Result<String, CompileError> {
+    let tokens = tokenize(src)?;
+    let Program { globals, functions } = Parser { tokens, pos: 0 }.program()?;
+
+    let mut global_names = HashSet::new();
+    for (name, _) in &globals {
+        if !global_names.insert(name.to_string()) {
+            return err(1, format!("duplicate global `{}`", name));
+        }
+    }
+    let mut arities = HashMap::new();
+    for f in &functions {
+        let is_builtin = BUILTINS.iter().any(|(builtin, _)| *builtin == f.name);
+        if is_builtin || arities.insert(f.name.to_string(), f.params.len()).is_some() {
+            return err(f.line, format!("duplicate function `{}`", f.name));
+        }
+        let unique: HashSet<&String> = f.params.iter().collect();
+        if unique.len() != f.params.len() {
+            return err(f.line, format!("duplicate param in `{}`", f.name));
+        }
+    }
+    if arities.get("main") != Some(&0) {
+        return err(1, "missing `fn main()`".to_string());
+    }
+
+    let mut codegen = Codegen {
+        lines: vec![],
+        next_label: 0,
+        globals: &global_names,
+        functions: &arities,
+    };
+    codegen.emit("arb #stack".to_string());
+    codegen.emit("add #exit, #0, [rb]".to_string());
+    codegen.emit("jz #0, #fn_main".to_string());
+    codegen.place("exit");
+    codegen.emit("halt".to_string());
+    for f in &functions {
+        codegen.function(f)?;
+    }
+    codegen.lines.push(String::new());
+    for (name, value) in &globals {
+        codegen.lines.push(format!("g_{}: data {}", name, value));
+    }
+    codegen.lines.push("ret:    data 0".to_string());
+    codegen.lines.push("stack:  data 0".to_string());
+    Ok(codegen.lines.join("\n") + "\n")
+}
+
+pub fn compile_prog(src: &str) -> Result<Prog, CompileError> {
+    Ok(assemble(&compile(src)?).expect("Compiled to invalid assembly"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Machine;
+
+    fn run(src: &str, inputs: Vec<isize>) -> Vec<isize> {
+        Machine::run_once(&compile_prog(src).unwrap(), inputs).unwrap()
+    }
+
+    #[test]
+    fn fibonacci() {
+        let src = "
+            // Outputs the first n Fibonacci numbers, then how many calls it took
+            let calls = 0;
+
+            fn fib(n) {
+                calls = calls + 1;
+                if n < 2 {
+                    return n;
+                }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            fn main() {
+                let n = read();
+                let i = 0;
+                while i < n {
+                    write(fib(i));
+                    i = i + 1;
+                }
+                write(calls);
+            }
+        ";
+        assert_eq!(
+            run(src, vec![10]),
+            vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34, 276]
+        );
+    }
+
+    #[test]
+    fn expressions() {
+        let src = "
+            let g = -3;
+
+            fn bump() {
+                g = g + 10;
+                return 1;
+            }
+
+            fn max(a, b) {
+                if a > b {
+                    return a;
+                } else if a == b {
+                    return 0 - 1;
+                } else {
+                    return b;
+                }
+            }
+
+            fn main() {
+                let a = read();
+                let b = read();
+                write(a - b * 2);
+                write(-(a + 1) * -2);
+                write((a < b) + (a <= a) * 10 + (a >= b) * 100 + (a != b) * 1000);
+                write(!a + !0 * 2);
+                write(max(a, b) + max(b, a) + max(a, a));
+                write(g + bump());
+                write(g);
+                let a = a * 100;
+                write(a);
+            }
+        ";
+        assert_eq!(run(src, vec![3, 7]), vec![-11, 8, 1011, 2, 13, -2, 7, 300]);
+    }
+
+    #[test]
+    fn errors() {
+        let compile_err = |src| compile(src).unwrap_err().to_string();
+        assert_eq!(
+            compile_err("fn main() {\n  write(x);\n}"),
+            "line 2: undefined variable `x`"
+        );
+        assert_eq!(
+            compile_err("fn main() {\n  f(1);\n}\nfn f() {}"),
+            "line 2: `f` takes 0 arg(s), found 1"
+        );
+        assert_eq!(
+            compile_err("fn main() {\n  let 1 = 2;\n}"),
+            "line 2: expected a name, found `1`"
+        );
+        assert_eq!(compile_err("fn f() {}"), "line 1: missing `fn main()`");
+        assert_eq!(
+            compile_err("fn main() {\n  write(1) }"),
+            "line 2: expected `;`, found `}`"
+        );
+        assert_eq!(compile_err("fn main() { $ }"), "line 1: unexpected `$`");
+    }
+}
@@ -0,0 +1,39 @@
+// Extra instructions registered on a machine (see `Machine::register_op`),
+// for trying out ISA extensions without touching the built-in ones.
+
+use crate::instruction::{ArgType, Opcode};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+// What a custom instruction does once its handler has run. Anything else
+// (ex. printing) the handler can do itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    Continue,
+    Store(usize, isize),
+    Output(isize),
+    Jump(usize),
+}
+
+// Called with the instruction's args resolved: values for `ArgType::Value`
+// args, addresses for `ArgType::Address` args.
+pub type Handler = dyn FnMut(&[isize]) -> Effect + Send;
+
+// Clones of a machine share their custom ops' handlers.
+#[derive(Clone)]
+pub struct CustomOp {
+    pub opcode: Opcode,
+    pub mnemonic: &'static str,
+    pub args: Vec<ArgType>,
+    pub(crate) handler: Arc<Mutex<Handler>>,
+}
+
+impl fmt::Debug for CustomOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CustomOp")
+            .field("opcode", &self.opcode)
+            .field("mnemonic", &self.mnemonic)
+            .field("args", &self.args)
+            .finish()
+    }
+}
@@ -0,0 +1,254 @@
+use crate::disasm::{decode, Instruction};
+use crate::{IntcodeError, Machine, Memory, Prog, State};
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stop {
+    Stepped,
+    Breakpoint(usize),
+    Watchpoint {
+        pc: usize,
+        address: usize,
+        old: isize,
+        new: isize,
+    },
+    NeedsInput,
+    Halted,
+    Error(IntcodeError),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Stepped => write!(f, "stepped"),
+            Stop::Breakpoint(pc) => write!(f, "breakpoint at {}", pc),
+            Stop::Watchpoint {
+                pc,
+                address,
+                old,
+                new,
+            } => write!(
+                f,
+                "watchpoint: [{}] {} -> {} (written by instruction at {})",
+                address, old, new, pc
+            ),
+            Stop::NeedsInput => write!(f, "waiting for input"),
+            Stop::Halted => write!(f, "halted"),
+            Stop::Error(e) => write!(f, "error: {}", e),
+        }
+    }
+}
+
+pub struct Debugger<M = Prog> {
+    pub machine: Machine<M>,
+    pub outputs: Vec<isize>,
+    breakpoints: HashSet<usize>,
+    watchpoints: HashSet<usize>,
+}
+
+impl<M: Memory> Debugger<M> {
+    pub fn new(machine: Machine<M>) -> Debugger<M> {
+        Debugger {
+            machine,
+            outputs: vec![],
+            breakpoints: HashSet::new(),
+            watchpoints: HashSet::new(),
+        }
+    }
+
+    pub fn breakpoints(&self) -> &HashSet<usize> {
+        &self.breakpoints
+    }
+
+    pub fn watchpoints(&self) -> &HashSet<usize> {
+        &self.watchpoints
+    }
+
+    pub fn add_breakpoint(&mut self, pc: usize) {
+        self.breakpoints.insert(pc);
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: usize) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize) {
+        self.watchpoints.insert(address);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address)
+    }
+
+    pub fn instruction(&self) -> Option<Instruction> {
+        decode(|address| self.machine.load(address), self.machine.pc)
+    }
+
+    // Watchpoints fire on every write an instruction makes to a watched
+    // address (custom ops' included), even one that leaves it unchanged.
+    // `old` is what memory held before, which for an address mapped to a
+    // device is the memory underneath it.
+    pub fn step(&mut self) -> Stop {
+        let pc = self.machine.pc;
+        let before: Vec<(usize, isize)> = self
+            .watchpoints
+            .iter()
+            .map(|&address| (address, self.machine.load(address)))
+            .collect();
+        self.machine.track_writes();
+        self.machine.take_writes();
+        let result = self.machine.step();
+        let writes = self.machine.take_writes();
+        match result {
+            Ok(Some(State::NeedsInput)) => return Stop::NeedsInput,
+            Ok(Some(State::Halted)) => return Stop::Halted,
+            Ok(Some(State::Output(o))) => self.outputs.push(o),
+            Ok(None) => (),
+            Err(e) => return Stop::Error(e),
+        }
+        writes
+            .into_iter()
+            .find_map(|(address, new)| {
+                let &(_, old) = before.iter().find(|&&(a, _)| a == address)?;
+                Some(Stop::Watchpoint {
+                    pc,
+                    address,
+                    old,
+                    new,
+                })
+            })
+            .unwrap_or(Stop::Stepped)
+    }
+
+    // Runs until a breakpoint/watchpoint is hit, input is needed or the
+    // program halts. Always executes at least one instruction, so continuing
+    // from a breakpoint moves past it.
+    pub fn cont(&mut self) -> Stop {
+        loop {
+            match self.step() {
+                Stop::Stepped => (),
+                stop => return stop,
+            }
+            if self.breakpoints.contains(&self.machine.pc) {
+                return Stop::Breakpoint(self.machine.pc);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::{read_prog, PagedMemory};
+
+    fn debugger(src: &str) -> Debugger<PagedMemory> {
+        Debugger::new(Machine::new(PagedMemory::from(&assemble(src).unwrap())))
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut d = debugger(
+            "
+                add #0, #0, [i]
+            loop:
+                add [i], #1, [i]
+                out [i]
+                lt [i], #3, [t]
+                jnz [t], #loop
+                halt
+            i:  data 0
+            t:  data 0
+            ",
+        );
+        d.add_breakpoint(10);
+        assert_eq!(d.cont(), Stop::Breakpoint(10));
+        assert_eq!(d.outputs, vec![1]);
+        assert_eq!(d.instruction().unwrap().to_string(), "lt [18], #3, [19]");
+        assert_eq!(d.cont(), Stop::Breakpoint(10));
+        assert_eq!(d.outputs, vec![1, 2]);
+
+        assert!(d.remove_breakpoint(10));
+        assert_eq!(d.cont(), Stop::Halted);
+        assert_eq!(d.outputs, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn watchpoints() {
+        let mut d = debugger(
+            "
+                arb #10
+                in [rb+2]
+                add [12], #5, [rb+3]
+                halt
+            ",
+        );
+        d.add_watchpoint(13);
+        assert_eq!(d.cont(), Stop::NeedsInput);
+        d.machine.push_input(4);
+        assert_eq!(
+            d.cont(),
+            Stop::Watchpoint {
+                pc: 4,
+                address: 13,
+                old: 0,
+                new: 9
+            }
+        );
+        assert_eq!(d.cont(), Stop::Halted);
+
+        // An input instruction with a stray mode digit still runs, and still
+        // trips the watchpoint
+        let mut d = Debugger::new(Machine::new(PagedMemory::from(&read_prog("1003,10,99"))));
+        d.add_watchpoint(10);
+        d.machine.push_input(5);
+        assert_eq!(
+            d.cont(),
+            Stop::Watchpoint {
+                pc: 0,
+                address: 10,
+                old: 0,
+                new: 5
+            }
+        );
+
+        // Writing the same value still counts
+        let mut d = debugger("add #0, #0, [x]\nhalt\nx: data 0");
+        d.add_watchpoint(5);
+        assert_eq!(
+            d.cont(),
+            Stop::Watchpoint {
+                pc: 0,
+                address: 5,
+                old: 0,
+                new: 0
+            }
+        );
+        assert_eq!(d.cont(), Stop::Halted);
+    }
+
+    #[test]
+    fn patch_and_step() {
+        let mut d = debugger("out [x]\nout [rb]\nhalt\nx: data 1");
+        d.machine.store(5, 42);
+        d.machine.base = 1;
+        assert_eq!(d.step(), Stop::Stepped);
+        assert_eq!(d.step(), Stop::Stepped);
+        assert_eq!(d.outputs, vec![42, 5]);
+        assert_eq!(d.step(), Stop::Halted);
+    }
+
+    #[test]
+    fn errors_stop_on_the_faulting_instruction() {
+        let mut d = debugger(
+            "out #1
+data 42",
+        );
+        assert_eq!(
+            d.cont(),
+            Stop::Error(IntcodeError::UnknownOpcode { pc: 2, raw: 42 })
+        );
+        assert_eq!(d.machine.pc, 2);
+    }
+}
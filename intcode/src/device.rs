@@ -0,0 +1,207 @@
+// Devices mapped into a machine's memory (see `Machine::attach`). The
+// program's loads and stores in a device's range go to the device instead of
+// memory, with addresses relative to the start of the range. Instructions are
+// always fetched from memory, and drivers' `load`/`store` bypass devices.
+
+use crate::rng::Rng;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+pub trait Device: Send {
+    // How many words it takes up. Offsets are always below this.
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn read(&mut self, offset: usize) -> isize;
+    fn write(&mut self, offset: usize, value: isize);
+    // Called after every instruction the machine executes
+    fn tick(&mut self) {}
+}
+
+// Clones of a machine share their devices.
+#[derive(Clone)]
+pub(crate) struct Attached {
+    pub start: usize,
+    pub len: usize,
+    pub device: Arc<Mutex<dyn Device>>,
+}
+
+impl Attached {
+    pub fn offset(&self, address: usize) -> Option<usize> {
+        if (self.start..self.start + self.len).contains(&address) {
+            Some(address - self.start)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Debug for Attached {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Attached")
+            .field("start", &self.start)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+// `width * height` words of pixels, row by row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<isize>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> isize {
+        self.pixels[y * self.width + x]
+    }
+
+    // One line per row, each pixel drawn as `draw` of its value
+    pub fn render(&self, draw: impl Fn(isize) -> char) -> String {
+        self.pixels
+            .chunks(self.width.max(1))
+            .map(|row| row.iter().map(|&p| draw(p)).collect::<String>() + "\n")
+            .collect()
+    }
+}
+
+impl Device for Framebuffer {
+    fn len(&self) -> usize {
+        self.pixels.len()
+    }
+
+    fn read(&mut self, offset: usize) -> isize {
+        self.pixels[offset]
+    }
+
+    fn write(&mut self, offset: usize, value: isize) {
+        self.pixels[offset] = value;
+    }
+}
+
+// One word counting the instructions executed, which the program can reset
+// by writing to it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timer {
+    pub ticks: isize,
+}
+
+impl Device for Timer {
+    fn len(&self) -> usize {
+        1
+    }
+
+    fn read(&mut self, _: usize) -> isize {
+        self.ticks
+    }
+
+    fn write(&mut self, _: usize, value: isize) {
+        self.ticks = value;
+    }
+
+    fn tick(&mut self) {
+        self.ticks = self.ticks.wrapping_add(1);
+    }
+}
+
+// Two words: reading the first gives a random number below the second (the
+// bound). Writing the first reseeds the generator.
+#[derive(Debug, Clone)]
+pub struct Random {
+    rng: Rng,
+    pub bound: isize,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random {
+            rng: Rng::new(seed),
+            bound: isize::MAX,
+        }
+    }
+}
+
+impl Device for Random {
+    fn len(&self) -> usize {
+        2
+    }
+
+    fn read(&mut self, offset: usize) -> isize {
+        match offset {
+            0 if self.bound <= 0 => 0,
+            0 => self.rng.range(0, self.bound),
+            _ => self.bound,
+        }
+    }
+
+    fn write(&mut self, offset: usize, value: isize) {
+        match offset {
+            0 => self.rng = Rng::new(value as u64),
+            _ => self.bound = value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::{Machine, VecIO};
+
+    #[test]
+    fn devices() {
+        // Draws a diagonal line, then outputs the timer and two dice rolls
+        let prog = assemble(
+            "
+                    add #0, #0, [1000]      ; reset the timer
+                    arb #100
+            loop:   add #7, #0, [rb]
+                    arb #4
+                    add [i], #1, [i]
+                    lt [i], #3, [t]
+                    jnz [t], #loop
+                    out [1000]
+                    add #6, #0, [2001]
+                    out [2000]
+                    out [2000]
+                    halt
+            i:      data 0
+            t:      data 0
+            ",
+        )
+        .unwrap();
+        let mut machine = Machine::new(prog);
+        let screen = machine.attach(100, Framebuffer::new(3, 3));
+        machine.attach(1000, Timer::default());
+        machine.attach(2000, Random::new(1));
+        let mut io = VecIO::default();
+        machine.run(&mut io).unwrap();
+
+        let screen = screen.lock().unwrap();
+        assert_eq!(
+            screen.render(|p| if p == 7 { '#' } else { '.' }),
+            "#..\n.#.\n..#\n"
+        );
+        assert_eq!(io.outputs[0], 2 + 3 * 5);
+        assert!(io.outputs[1..].iter().all(|roll| (0..6).contains(roll)));
+        // Memory under the devices is untouched
+        assert_eq!(machine.load(104), 0);
+
+        // A device only takes up its own length
+        let mut machine = Machine::new(assemble("add #5, #0, [109]\nhalt").unwrap());
+        machine.attach(100, Framebuffer::new(3, 3));
+        machine.run(&mut VecIO::default()).unwrap();
+        assert_eq!(machine.load(109), 5);
+    }
+}
@@ -0,0 +1,386 @@
+// Differential testing: random programs are run on a deliberately simple
+// reference interpreter and on each machine configuration, and any difference
+// in outputs, how the run ended or the final memory is reported.
+//
+// Random programs are made of valid instructions (with the odd invalid one to
+// exercise errors) that read and write near the program, including over its
+// own code, and jump to instruction boundaries. Many never halt, so every run
+// is cut off after a number of steps.
+
+use crate::instruction::{ArgType, OPS};
+use crate::rng::Rng;
+use crate::{IntcodeError, Machine, Memory, PagedMemory, Prog, State};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub fn random_program(rng: &mut Rng) -> Prog {
+    let ops: Vec<_> = OPS.iter().filter(|info| info.opcode != 99).collect();
+    let count = rng.range(4, 24) as usize;
+    // Pick instructions first, so jumps know where instructions start
+    let picked: Vec<_> = (0..count)
+        .map(|_| ops[rng.range(0, ops.len() as isize) as usize])
+        .collect();
+    let mut starts = vec![];
+    let mut len = 0;
+    for info in &picked {
+        starts.push(len as isize);
+        len += 1 + info.args.len();
+    }
+    let len = len as isize;
+
+    let mut words = vec![];
+    for info in picked {
+        let mut raw = info.opcode;
+        let mut params = vec![];
+        for (i, arg_type) in info.args.iter().enumerate() {
+            let is_jump_target = (info.opcode == 5 || info.opcode == 6) && i == 1;
+            let writes = *arg_type == ArgType::Address;
+            let mode = match rng.range(0, 3) {
+                2 => 2,
+                1 if !writes => 1,
+                _ if is_jump_target && !rng.one_in(4) => 1,
+                _ => 0,
+            };
+            let param = match mode {
+                1 if is_jump_target => starts[rng.range(0, starts.len() as isize) as usize],
+                1 if rng.one_in(20) => rng.range(1 << 32, 1 << 40),
+                1 => rng.range(-10, 10),
+                2 => rng.range(-4, 8),
+                _ if rng.one_in(50) => rng.range(-3, 0),
+                _ if rng.one_in(50) => rng.range(4000, 5000),
+                _ => rng.range(0, len + 8),
+            };
+            raw += mode * 10_isize.pow(2 + i as u32);
+            params.push(param);
+        }
+        if rng.one_in(100) {
+            raw = rng.range(10, 99);
+        } else if rng.one_in(100) {
+            raw += 3 * 100;
+        }
+        words.push(raw);
+        words.extend(params);
+    }
+    words.push(99);
+    words.into_iter().enumerate().collect()
+}
+
+pub fn random_inputs(rng: &mut Rng) -> Vec<isize> {
+    (0..rng.range(0, 6)).map(|_| rng.range(-5, 10)).collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum End {
+    Halted,
+    Failed(IntcodeError),
+    OutOfSteps,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub outputs: Vec<isize>,
+    pub end: End,
+    // Every non-zero word, ordered by address
+    pub memory: Vec<(usize, isize)>,
+}
+
+// Straight from the puzzle descriptions, sharing nothing with `Machine` but
+// the error type.
+struct Reference<'a> {
+    memory: Prog,
+    pc: usize,
+    base: isize,
+    inputs: std::slice::Iter<'a, isize>,
+    outputs: Vec<isize>,
+}
+
+impl Reference<'_> {
+    fn load(&self, address: usize) -> isize {
+        *self.memory.get(&address).unwrap_or(&0)
+    }
+
+    fn fail(&self, error: fn(usize, isize) -> IntcodeError) -> End {
+        End::Failed(error(self.pc, self.load(self.pc)))
+    }
+
+    fn address(&self, address: isize) -> Result<usize, End> {
+        if address < 0 {
+            let (pc, raw) = (self.pc, self.load(self.pc));
+            return Err(End::Failed(IntcodeError::NegativeAddress {
+                pc,
+                raw,
+                address,
+            }));
+        }
+        Ok(address as usize)
+    }
+
+    fn relative(&self, offset: isize) -> Result<usize, End> {
+        match self.base.checked_add(offset) {
+            Some(address) => self.address(address),
+            None => Err(self.fail(|pc, raw| IntcodeError::Overflow { pc, raw })),
+        }
+    }
+
+    fn mode(&self, i: usize) -> isize {
+        self.load(self.pc) / 10_isize.pow(2 + i as u32) % 10
+    }
+
+    fn param(&self, i: usize) -> isize {
+        self.load(self.pc + 1 + i)
+    }
+
+    fn get(&self, i: usize) -> Result<isize, End> {
+        Ok(match self.mode(i) {
+            0 => self.load(self.address(self.param(i))?),
+            1 => self.param(i),
+            _ => self.load(self.relative(self.param(i))?),
+        })
+    }
+
+    fn set(&mut self, i: usize, value: isize) -> Result<(), End> {
+        let address = match self.mode(i) {
+            2 => self.relative(self.param(i))?,
+            _ => self.address(self.param(i))?,
+        };
+        self.memory.insert(address, value);
+        Ok(())
+    }
+
+    // Runs one instruction, returning how the run ended if it did
+    fn step(&mut self) -> Result<(), End> {
+        let raw = self.load(self.pc);
+        let op = raw % 100;
+        let param_count = match op {
+            1 | 2 | 7 | 8 => 3,
+            5 | 6 => 2,
+            3 | 4 | 9 => 1,
+            99 => return Err(End::Halted),
+            _ => return Err(self.fail(|pc, raw| IntcodeError::UnknownOpcode { pc, raw })),
+        };
+        if let Some(param) = (0..param_count).find(|&i| self.mode(i) > 2) {
+            let pc = self.pc;
+            return Err(End::Failed(IntcodeError::InvalidParamMode {
+                pc,
+                raw,
+                param,
+            }));
+        }
+        let overflow = |r: &Self| r.fail(|pc, raw| IntcodeError::Overflow { pc, raw });
+        let mut next = self.pc + 1 + param_count;
+        match op {
+            1 => {
+                let sum = self.get(0)?.checked_add(self.get(1)?);
+                self.set(2, sum.ok_or_else(|| overflow(self))?)?;
+            }
+            2 => {
+                let product = self.get(0)?.checked_mul(self.get(1)?);
+                self.set(2, product.ok_or_else(|| overflow(self))?)?;
+            }
+            3 => match self.inputs.next() {
+                Some(&input) => self.set(0, input)?,
+                None => return Err(self.fail(|pc, raw| IntcodeError::NoInput { pc, raw })),
+            },
+            4 => {
+                let output = self.get(0)?;
+                self.outputs.push(output);
+            }
+            5 | 6 => {
+                if (self.get(0)? != 0) == (op == 5) {
+                    let target = self.get(1)?;
+                    if target < 0 {
+                        let pc = self.pc;
+                        return Err(End::Failed(IntcodeError::NegativeJump { pc, raw, target }));
+                    }
+                    next = target as usize;
+                }
+            }
+            7 => self.set(2, (self.get(0)? < self.get(1)?) as isize)?,
+            8 => self.set(2, (self.get(0)? == self.get(1)?) as isize)?,
+            _ => {
+                let base = self.base.checked_add(self.get(0)?);
+                self.base = base.ok_or_else(|| overflow(self))?;
+                if self.base < 0 {
+                    let (pc, base) = (self.pc, self.base);
+                    return Err(End::Failed(IntcodeError::NegativeBase { pc, raw, base }));
+                }
+            }
+        }
+        self.pc = next;
+        Ok(())
+    }
+}
+
+pub fn run_reference(prog: &Prog, inputs: &[isize], max_steps: usize) -> Run {
+    let mut reference = Reference {
+        memory: prog.clone(),
+        pc: 0,
+        base: 0,
+        inputs: inputs.iter(),
+        outputs: vec![],
+    };
+    let mut end = End::OutOfSteps;
+    for _ in 0..max_steps {
+        if let Err(e) = reference.step() {
+            end = e;
+            break;
+        }
+    }
+    Run {
+        outputs: reference.outputs,
+        end,
+        memory: reference.memory.words(),
+    }
+}
+
+pub fn run_machine<M: Memory>(mut machine: Machine<M>, inputs: &[isize], max_steps: usize) -> Run {
+    for &input in inputs {
+        machine.push_input(input);
+    }
+    let mut outputs = vec![];
+    let mut end = End::OutOfSteps;
+    for _ in 0..max_steps {
+        match machine.step() {
+            Ok(None) => (),
+            Ok(Some(State::Output(o))) => outputs.push(o),
+            Ok(Some(State::Halted)) => {
+                end = End::Halted;
+                break;
+            }
+            Ok(Some(State::NeedsInput)) => {
+                let (pc, raw) = (machine.pc, machine.load(machine.pc));
+                end = End::Failed(IntcodeError::NoInput { pc, raw });
+                break;
+            }
+            Err(e) => {
+                end = End::Failed(e);
+                break;
+            }
+        }
+    }
+    Run {
+        outputs,
+        end,
+        memory: machine.memory().words(),
+    }
+}
+
+// Every machine configuration, by name
+pub fn run_machines(prog: &Prog, inputs: &[isize], max_steps: usize) -> Vec<(&'static str, Run)> {
+    let mut runs = vec![];
+    for &cached in &[true, false] {
+        let mut sparse = Machine::new(prog.clone());
+        let mut paged = Machine::new(PagedMemory::from(prog));
+        sparse.set_decode_cache(cached);
+        paged.set_decode_cache(cached);
+        let (sparse_name, paged_name) = if cached {
+            ("sparse", "paged")
+        } else {
+            ("sparse uncached", "paged uncached")
+        };
+        runs.push((sparse_name, run_machine(sparse, inputs, max_steps)));
+        runs.push((paged_name, run_machine(paged, inputs, max_steps)));
+    }
+    runs
+}
+
+pub fn first_divergence(expected: &Run, actual: &Run) -> Option<String> {
+    let outputs = expected.outputs.iter().zip(&actual.outputs).enumerate();
+    for (i, (e, a)) in outputs {
+        if e != a {
+            return Some(format!("output {} is {}, expected {}", i, a, e));
+        }
+    }
+    if expected.outputs.len() != actual.outputs.len() {
+        return Some(format!(
+            "{} outputs, expected {}",
+            actual.outputs.len(),
+            expected.outputs.len()
+        ));
+    }
+    if expected.end != actual.end {
+        return Some(format!(
+            "ended {:?}, expected {:?}",
+            actual.end, expected.end
+        ));
+    }
+    let (expected, actual): (BTreeMap<_, _>, BTreeMap<_, _>) = (
+        expected.memory.iter().cloned().collect(),
+        actual.memory.iter().cloned().collect(),
+    );
+    for address in expected
+        .keys()
+        .chain(actual.keys())
+        .collect::<BTreeSet<_>>()
+    {
+        let (e, a) = (expected.get(address), actual.get(address));
+        if e != a {
+            return Some(format!(
+                "memory [{}] is {}, expected {}",
+                address,
+                a.unwrap_or(&0),
+                e.unwrap_or(&0)
+            ));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::listing;
+
+    #[test]
+    fn machines_match_reference() {
+        for seed in 0..2000 {
+            let mut rng = Rng::new(seed);
+            let prog = random_program(&mut rng);
+            let inputs = random_inputs(&mut rng);
+            let expected = run_reference(&prog, &inputs, 500);
+            for (name, run) in run_machines(&prog, &inputs, 500) {
+                if let Some(divergence) = first_divergence(&expected, &run) {
+                    panic!(
+                        "seed {}, {}: {}\ninputs {:?}\n{}",
+                        seed,
+                        name,
+                        divergence,
+                        inputs,
+                        listing(&prog)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reports_divergence() {
+        let run = |outputs, end, memory| Run {
+            outputs,
+            end,
+            memory,
+        };
+        let expected = run(vec![1, 2], End::Halted, vec![(0, 99), (7, 3)]);
+        let check = |actual: Run| first_divergence(&expected, &actual);
+        assert_eq!(check(expected.clone()), None);
+        assert_eq!(
+            check(run(vec![1, 3], End::Halted, vec![])),
+            Some("output 1 is 3, expected 2".to_string())
+        );
+        assert_eq!(
+            check(run(vec![1], End::Halted, vec![])),
+            Some("1 outputs, expected 2".to_string())
+        );
+        assert_eq!(
+            check(run(vec![1, 2], End::OutOfSteps, vec![])),
+            Some("ended OutOfSteps, expected Halted".to_string())
+        );
+        assert_eq!(
+            check(run(vec![1, 2], End::Halted, vec![(0, 99), (5, 1), (7, 3)])),
+            Some("memory [5] is 1, expected 0".to_string())
+        );
+        assert_eq!(
+            check(run(vec![1, 2], End::Halted, vec![(0, 99)])),
+            Some("memory [7] is 0, expected 3".to_string())
+        );
+    }
+}
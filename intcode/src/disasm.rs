@@ -0,0 +1,233 @@
+// Decodes programs back into the assembler's syntax (see `asm`).
+//
+//      0: mul #34463338, #34463338, [63]   ; 1102,34463338,34463338,63
+//      4: lt [63], #34463338, [63]         ; 1007,63,34463338,63
+//    ...
+//    999: data 7                           ; 7
+//
+// Words that don't decode to a valid instruction are listed as data.
+
+use crate::instruction::{op_info, opcode, param_mode, ArgType, ParamMode};
+use crate::Prog;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub arg_type: ArgType,
+    pub mode: ParamMode,
+    // The mode digit as written, since 0 and 1 are the same mode for params
+    // written to
+    pub digit: isize,
+    pub raw: isize,
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.arg_type, self.mode) {
+            (ArgType::Value, ParamMode::Immediate) => write!(f, "#{}", self.raw),
+            (ArgType::Address, _) if self.digit == 1 => write!(f, "[#{}]", self.raw),
+            (_, ParamMode::RelativeToBaseValue) | (_, ParamMode::RelativeToBaseAddress) => {
+                match self.raw {
+                    0 => write!(f, "[rb]"),
+                    n if n < 0 => write!(f, "[rb{}]", n),
+                    n => write!(f, "[rb+{}]", n),
+                }
+            }
+            _ => write!(f, "[{}]", self.raw),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub address: usize,
+    pub raw: isize,
+    pub mnemonic: &'static str,
+    pub params: Vec<Param>,
+}
+
+impl Instruction {
+    pub fn opcode(&self) -> isize {
+        opcode(self.raw)
+    }
+
+    pub fn size(&self) -> usize {
+        1 + self.params.len()
+    }
+
+    pub fn words(&self) -> Vec<isize> {
+        let mut words = vec![self.raw];
+        words.extend(self.params.iter().map(|p| p.raw));
+        words
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic)?;
+        for (i, p) in self.params.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, p)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn decode(load: impl Fn(usize) -> isize, address: usize) -> Option<Instruction> {
+    let raw = load(address);
+    if raw < 0 {
+        return None;
+    }
+    let info = op_info(opcode(raw))?;
+    // Stray mode digits beyond the last param are ignored when executing, but
+    // are a good sign these words are data.
+    if raw / 10_isize.pow(2 + info.args.len() as u32) != 0 {
+        return None;
+    }
+    let params = info
+        .args
+        .iter()
+        .enumerate()
+        .map(|(i, arg_type)| {
+            Some(Param {
+                arg_type: *arg_type,
+                mode: param_mode(raw, i, arg_type)?,
+                digit: raw / 10_isize.pow(2 + i as u32) % 10,
+                raw: load(address + 1 + i),
+            })
+        })
+        .collect::<Option<Vec<Param>>>()?;
+    Some(Instruction {
+        address,
+        raw,
+        mnemonic: info.mnemonic,
+        params,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line {
+    Instruction(Instruction),
+    Data { address: usize, value: isize },
+}
+
+impl Line {
+    pub fn address(&self) -> usize {
+        match self {
+            Line::Instruction(inst) => inst.address,
+            Line::Data { address, .. } => *address,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        match self {
+            Line::Instruction(inst) => inst.to_string(),
+            Line::Data { value, .. } => format!("data {}", value),
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let words = match self {
+            Line::Instruction(inst) => inst.words(),
+            Line::Data { value, .. } => vec![*value],
+        };
+        let words = words
+            .iter()
+            .map(isize::to_string)
+            .collect::<Vec<String>>()
+            .join(",");
+        write!(f, "{:>6}: {:<32} ; {}", self.address(), self.text(), words)
+    }
+}
+
+pub fn disassemble(prog: &Prog) -> Vec<Line> {
+    let len = prog.keys().max().map_or(0, |&max| max + 1);
+    let load = |address: usize| *prog.get(&address).unwrap_or(&0);
+    let mut lines = vec![];
+    let mut address = 0;
+    while address < len {
+        match decode(load, address) {
+            Some(inst) if address + inst.size() <= len => {
+                address += inst.size();
+                lines.push(Line::Instruction(inst));
+            }
+            _ => {
+                lines.push(Line::Data {
+                    address,
+                    value: load(address),
+                });
+                address += 1;
+            }
+        }
+    }
+    lines
+}
+
+pub fn listing(prog: &Prog) -> String {
+    disassemble(prog)
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::read_prog;
+
+    #[test]
+    fn annotates_modes() {
+        let text = |prog| {
+            disassemble(&read_prog(prog))
+                .iter()
+                .map(Line::text)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            text("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99"),
+            vec![
+                "arb #1",
+                "out [rb-1]",
+                "add [100], #1, [100]",
+                "eq [100], #16, [101]",
+                "jz [101], #0",
+                "halt"
+            ]
+        );
+        assert_eq!(
+            text("21101,3,4,0,42,-7,1002"),
+            vec!["add #3, #4, [rb]", "data 42", "data -7", "data 1002"]
+        );
+        // Explicitly immediate writes keep their mode digit
+        assert_eq!(text("11101,1,2,3"), vec!["add #1, #2, [#3]"]);
+        assert_eq!(
+            assemble("add #1, #2, [#3]").unwrap(),
+            read_prog("11101,1,2,3")
+        );
+    }
+
+    #[test]
+    fn round_trips_through_asm() {
+        let src = "in [rb+3]\nlt #1, [9], [rb-1]\njnz [rb], #0\nhalt\ndata 5";
+        let prog = assemble(src).unwrap();
+        let lines = disassemble(&prog)
+            .iter()
+            .map(Line::text)
+            .collect::<Vec<String>>()
+            .join("\n");
+        assert_eq!(lines, src);
+    }
+
+    #[test]
+    fn listing_shows_addresses_and_words() {
+        assert_eq!(
+            listing(&read_prog("104,7,99")),
+            format!(
+                "{:>6}: {:<32} ; 104,7\n{:>6}: {:<32} ; 99\n",
+                0, "out #7", 2, "halt"
+            )
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use crate::State;
+use std::fmt;
+
+// Every error carries the pc and raw opcode word of the faulting instruction.
+// The machine's pc is left on that instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntcodeError {
+    UnknownOpcode {
+        pc: usize,
+        raw: isize,
+    },
+    InvalidParamMode {
+        pc: usize,
+        raw: isize,
+        param: usize,
+    },
+    NegativeAddress {
+        pc: usize,
+        raw: isize,
+        address: isize,
+    },
+    NegativeJump {
+        pc: usize,
+        raw: isize,
+        target: isize,
+    },
+    NegativeBase {
+        pc: usize,
+        raw: isize,
+        base: isize,
+    },
+    NoInput {
+        pc: usize,
+        raw: isize,
+    },
+    Overflow {
+        pc: usize,
+        raw: isize,
+    },
+    // A driver expected output (see `Machine::expect_output`), but the
+    // machine stopped for something else
+    UnexpectedState {
+        pc: usize,
+        raw: isize,
+        state: State,
+    },
+}
+
+impl IntcodeError {
+    pub fn pc(&self) -> usize {
+        use IntcodeError::*;
+        match *self {
+            UnknownOpcode { pc, .. }
+            | InvalidParamMode { pc, .. }
+            | NegativeAddress { pc, .. }
+            | NegativeJump { pc, .. }
+            | NegativeBase { pc, .. }
+            | NoInput { pc, .. }
+            | Overflow { pc, .. }
+            | UnexpectedState { pc, .. } => pc,
+        }
+    }
+
+    pub fn raw(&self) -> isize {
+        use IntcodeError::*;
+        match *self {
+            UnknownOpcode { raw, .. }
+            | InvalidParamMode { raw, .. }
+            | NegativeAddress { raw, .. }
+            | NegativeJump { raw, .. }
+            | NegativeBase { raw, .. }
+            | NoInput { raw, .. }
+            | Overflow { raw, .. }
+            | UnexpectedState { raw, .. } => raw,
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use IntcodeError::*;
+        match self {
+            UnknownOpcode { .. } => write!(f, "unknown opcode"),
+            InvalidParamMode { param, .. } => write!(f, "invalid mode for parameter {}", param + 1),
+            NegativeAddress { address, .. } => write!(f, "negative address {}", address),
+            NegativeJump { target, .. } => write!(f, "jump to negative address {}", target),
+            NegativeBase { base, .. } => write!(f, "relative base adjusted to {}", base),
+            NoInput { .. } => write!(f, "no input available"),
+            Overflow { .. } => write!(f, "arithmetic overflow"),
+            UnexpectedState { state, .. } => write!(f, "expected output, but got {:?}", state),
+        }?;
+        write!(f, " (instruction {} at pc {})", self.raw(), self.pc())
+    }
+}
+
+impl std::error::Error for IntcodeError {}
+
+// The reason part of an `IntcodeError`, before the faulting instruction is
+// attached.
+#[derive(Debug)]
+pub(crate) enum Fault {
+    UnknownOpcode,
+    InvalidParamMode(usize),
+    NegativeAddress(isize),
+    NegativeJump(isize),
+    NegativeBase(isize),
+    NoInput,
+    Overflow,
+}
+
+impl Fault {
+    pub(crate) fn at(self, pc: usize, raw: isize) -> IntcodeError {
+        match self {
+            Fault::UnknownOpcode => IntcodeError::UnknownOpcode { pc, raw },
+            Fault::InvalidParamMode(param) => IntcodeError::InvalidParamMode { pc, raw, param },
+            Fault::NegativeAddress(address) => IntcodeError::NegativeAddress { pc, raw, address },
+            Fault::NegativeJump(target) => IntcodeError::NegativeJump { pc, raw, target },
+            Fault::NegativeBase(base) => IntcodeError::NegativeBase { pc, raw, base },
+            Fault::NoInput => IntcodeError::NoInput { pc, raw },
+            Fault::Overflow => IntcodeError::Overflow { pc, raw },
+        }
+    }
+}
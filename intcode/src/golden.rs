@@ -0,0 +1,387 @@
+// Golden IO: recording every input and output a program exchanges through
+// `InputOutput`, and replaying the recording to check a later run exchanges
+// exactly the same. Recordings are saved as text, consecutive inputs or
+// outputs sharing a line:
+//
+//     intcode-io 1
+//     out 1,2,3
+//     in 0
+//     out 6,5,4,-1,0,12
+//
+// Drivers using `InputOutput` record with `Recorder`, and drivers using
+// `resume` with `Recording`. A recording's inputs can also drive the program on
+// their own (see `Script::inputs`), without whatever produced them.
+
+use crate::{InputOutput, IntcodeError, Machine, Memory, Prog, State};
+use std::fmt;
+use std::fs::{read_to_string, write};
+use std::io;
+use std::path::Path;
+
+const HEADER: &str = "intcode-io 1";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Exchange {
+    Input(isize),
+    Output(isize),
+}
+
+impl fmt::Display for Exchange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Exchange::Input(v) => write!(f, "in {}", v),
+            Exchange::Output(v) => write!(f, "out {}", v),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Script {
+    pub exchanges: Vec<Exchange>,
+}
+
+fn invalid(line: usize, message: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("script line {}: {}", line, message),
+    )
+}
+
+impl Script {
+    pub fn inputs(&self) -> Vec<isize> {
+        self.exchanges
+            .iter()
+            .filter_map(|e| match e {
+                Exchange::Input(v) => Some(*v),
+                Exchange::Output(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut s = format!("{}\n", HEADER);
+        let mut exchanges = self.exchanges.iter().peekable();
+        while let Some(&first) = exchanges.next() {
+            let is_input = matches!(first, Exchange::Input(_));
+            let mut values = vec![first];
+            while let Some(&&e) = exchanges.peek() {
+                if matches!(e, Exchange::Input(_)) != is_input {
+                    break;
+                }
+                values.push(e);
+                exchanges.next();
+            }
+            let values: Vec<String> = values
+                .iter()
+                .map(|e| match e {
+                    Exchange::Input(v) | Exchange::Output(v) => v.to_string(),
+                })
+                .collect();
+            let kind = if is_input { "in" } else { "out" };
+            s += &format!("{} {}\n", kind, values.join(","));
+        }
+        s
+    }
+
+    pub fn from_text(text: &str) -> io::Result<Script> {
+        let mut lines = text.lines().enumerate().map(|(i, l)| (i + 1, l.trim()));
+        match lines.next() {
+            Some((_, HEADER)) => (),
+            _ => return Err(invalid(1, format!("expected `{}`", HEADER))),
+        }
+        let mut script = Script::default();
+        for (line, s) in lines.filter(|(_, s)| !s.is_empty()) {
+            let mut parts = s.splitn(2, ' ');
+            let exchange = match parts.next().unwrap() {
+                "in" => Exchange::Input,
+                "out" => Exchange::Output,
+                key => return Err(invalid(line, format!("unknown entry `{}`", key))),
+            };
+            for v in parts.next().unwrap_or("").split(',') {
+                let v = v.trim();
+                let v = v
+                    .parse()
+                    .map_err(|_| invalid(line, format!("invalid number `{}`", v)))?;
+                script.exchanges.push(exchange(v));
+            }
+        }
+        Ok(script)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        write(path, self.to_text())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Script> {
+        Script::from_text(&read_to_string(path)?)
+    }
+}
+
+// Passes everything through to `io`, recording it.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder<IO> {
+    pub io: IO,
+    pub script: Script,
+}
+
+impl<IO: InputOutput> Recorder<IO> {
+    pub fn new(io: IO) -> Recorder<IO> {
+        Recorder {
+            io,
+            script: Script::default(),
+        }
+    }
+}
+
+impl<IO: InputOutput> InputOutput for Recorder<IO> {
+    fn input(&mut self) -> Option<isize> {
+        let input = self.io.input()?;
+        self.script.exchanges.push(Exchange::Input(input));
+        Some(input)
+    }
+
+    fn output(&mut self, o: isize) {
+        self.script.exchanges.push(Exchange::Output(o));
+        self.io.output(o);
+    }
+}
+
+// A machine driven with `resume`, recording the inputs pushed to it and the
+// outputs it gives.
+#[derive(Debug, Clone)]
+pub struct Recording<M = Prog> {
+    pub machine: Machine<M>,
+    pub script: Script,
+}
+
+impl<M: Memory> Recording<M> {
+    pub fn new(machine: Machine<M>) -> Recording<M> {
+        Recording {
+            machine,
+            script: Script::default(),
+        }
+    }
+
+    pub fn resume(&mut self) -> Result<State, IntcodeError> {
+        let state = self.machine.resume()?;
+        if let State::Output(o) = state {
+            self.script.exchanges.push(Exchange::Output(o));
+        }
+        Ok(state)
+    }
+
+    pub fn expect_output(&mut self) -> Result<isize, IntcodeError> {
+        let o = self.machine.expect_output()?;
+        self.script.exchanges.push(Exchange::Output(o));
+        Ok(o)
+    }
+
+    pub fn push_input(&mut self, input: isize) {
+        self.script.exchanges.push(Exchange::Input(input));
+        self.machine.push_input(input);
+    }
+}
+
+// What the program did instead of the expected exchange
+#[derive(Debug, Clone, PartialEq)]
+pub enum Actual {
+    Input,
+    Output(isize),
+    Halted,
+    Failed(IntcodeError),
+}
+
+impl fmt::Display for Actual {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Actual::Input => write!(f, "asked for input"),
+            Actual::Output(v) => write!(f, "output {}", v),
+            Actual::Halted => write!(f, "halted"),
+            Actual::Failed(e) => write!(f, "failed: {}", e),
+        }
+    }
+}
+
+// The first exchange (by index into the script) that didn't match.
+// `expected` is `None` past the end of the script.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub index: usize,
+    pub expected: Option<Exchange>,
+    pub actual: Actual,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.expected {
+            Some(e) => write!(f, "exchange {}: expected {}", self.index, e)?,
+            None => write!(f, "exchange {}: expected the end", self.index)?,
+        }
+        write!(f, ", but the program {}", self.actual)
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+// Gives the program the script's inputs, checking its outputs along the way.
+struct Replay<'a> {
+    script: &'a Script,
+    next: usize,
+    mismatch: Option<Mismatch>,
+}
+
+impl Replay<'_> {
+    fn mismatch(&mut self, actual: Actual) {
+        if self.mismatch.is_none() {
+            self.mismatch = Some(Mismatch {
+                index: self.next,
+                expected: self.script.exchanges.get(self.next).copied(),
+                actual,
+            });
+        }
+    }
+
+    fn input(&mut self) -> Option<isize> {
+        match self.script.exchanges.get(self.next) {
+            Some(&Exchange::Input(v)) => {
+                self.next += 1;
+                Some(v)
+            }
+            _ => {
+                self.mismatch(Actual::Input);
+                None
+            }
+        }
+    }
+
+    fn output(&mut self, o: isize) {
+        match self.script.exchanges.get(self.next) {
+            Some(&Exchange::Output(v)) if v == o => self.next += 1,
+            _ => self.mismatch(Actual::Output(o)),
+        }
+    }
+}
+
+// Runs `machine` against `script`, until it halts or the first mismatch,
+// stopping it right there.
+pub fn replay<M: Memory>(machine: &mut Machine<M>, script: &Script) -> Result<(), Mismatch> {
+    let mut replay = Replay {
+        script,
+        next: 0,
+        mismatch: None,
+    };
+    while replay.mismatch.is_none() {
+        match machine.resume() {
+            Ok(State::NeedsInput) => {
+                if let Some(input) = replay.input() {
+                    machine.push_input(input);
+                }
+            }
+            Ok(State::Output(o)) => replay.output(o),
+            Ok(State::Halted) if replay.next < script.exchanges.len() => {
+                replay.mismatch(Actual::Halted)
+            }
+            Ok(State::Halted) => return Ok(()),
+            Err(e) => replay.mismatch(Actual::Failed(e)),
+        }
+    }
+    replay.mismatch.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::{read_prog, VecIO};
+
+    // Outputs each input doubled until it's given a 0
+    fn doubler() -> Machine {
+        let prog = assemble(
+            "
+            loop:   in [x]
+                    jz [x], #done
+                    mul [x], #2, [x]
+                    out [x]
+                    jz #0, #loop
+            done:   halt
+            x:      data 0
+            ",
+        );
+        Machine::new(prog.unwrap())
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let mut recorder = Recorder::new(VecIO::new(vec![3, 5, 0]));
+        doubler().run(&mut recorder).unwrap();
+        assert_eq!(recorder.io.outputs, vec![6, 10]);
+        let text = recorder.script.to_text();
+        assert_eq!(text, "intcode-io 1\nin 3\nout 6\nin 5\nout 10\nin 0\n");
+
+        let script = Script::from_text(&text).unwrap();
+        assert_eq!(script, recorder.script);
+        assert_eq!(script.inputs(), vec![3, 5, 0]);
+        assert_eq!(replay(&mut doubler(), &script), Ok(()));
+    }
+
+    #[test]
+    fn recording() {
+        let mut recording = Recording::new(doubler());
+        let mut inputs = vec![0, 4];
+        loop {
+            match recording.resume().unwrap() {
+                State::NeedsInput => recording.push_input(inputs.pop().unwrap()),
+                State::Output(_) => (),
+                State::Halted => break,
+            }
+        }
+        let script = recording.script;
+        assert_eq!(script.to_text(), "intcode-io 1\nin 4\nout 8\nin 0\n");
+        assert_eq!(replay(&mut doubler(), &script), Ok(()));
+    }
+
+    #[test]
+    fn mismatches() {
+        let script = |text: &str| Script::from_text(&format!("intcode-io 1\n{}", text)).unwrap();
+        assert_eq!(
+            replay(&mut doubler(), &script("in 3\nout 7\nin 0"))
+                .unwrap_err()
+                .to_string(),
+            "exchange 1: expected out 7, but the program output 6"
+        );
+        assert_eq!(
+            replay(&mut doubler(), &script("in 3\nin 0")).unwrap_err(),
+            Mismatch {
+                index: 1,
+                expected: Some(Exchange::Input(0)),
+                actual: Actual::Output(6)
+            }
+        );
+        assert_eq!(
+            replay(&mut doubler(), &script("in 0\nout 1"))
+                .unwrap_err()
+                .actual,
+            Actual::Halted
+        );
+        assert_eq!(
+            replay(&mut doubler(), &script("in 1\nout 2")).unwrap_err(),
+            Mismatch {
+                index: 2,
+                expected: None,
+                actual: Actual::Input
+            }
+        );
+        assert!(Script::from_text("intcode-io 1\nin x").is_err());
+
+        // Stops at the first wrong output, even if the program never reads
+        let mut forever = Machine::new(read_prog("104,1,1105,1,0"));
+        assert_eq!(
+            replay(&mut forever, &script("out 1\nout 2")).unwrap_err(),
+            Mismatch {
+                index: 1,
+                expected: Some(Exchange::Output(2)),
+                actual: Actual::Output(1)
+            }
+        );
+    }
+}
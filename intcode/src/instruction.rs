@@ -0,0 +1,105 @@
+pub type Opcode = isize;
+
+pub fn opcode(raw: isize) -> Opcode {
+    raw % 100
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamMode {
+    Position,
+    Immediate,
+    RelativeToBaseValue,
+    RelativeToBaseAddress,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgType {
+    Address,
+    Value,
+}
+
+pub fn param_mode(op: isize, argi: usize, arg_mode: &ArgType) -> Option<ParamMode> {
+    let param_modes = op / 100; // skip over opcode
+    let param_mode_code = (param_modes / 10_isize.pow(argi as u32)) % 10;
+    match arg_mode {
+        ArgType::Address => match param_mode_code {
+            0 => Some(ParamMode::Immediate),
+            1 => Some(ParamMode::Immediate),
+            2 => Some(ParamMode::RelativeToBaseAddress),
+            _ => None,
+        },
+        ArgType::Value => match param_mode_code {
+            0 => Some(ParamMode::Position),
+            1 => Some(ParamMode::Immediate),
+            2 => Some(ParamMode::RelativeToBaseValue),
+            _ => None,
+        },
+    }
+}
+
+pub struct OpInfo {
+    pub opcode: Opcode,
+    pub mnemonic: &'static str,
+    pub args: &'static [ArgType],
+}
+
+use ArgType::{Address, Value};
+
+pub const OPS: [OpInfo; 10] = [
+    OpInfo {
+        opcode: 1,
+        mnemonic: "add",
+        args: &[Value, Value, Address],
+    },
+    OpInfo {
+        opcode: 2,
+        mnemonic: "mul",
+        args: &[Value, Value, Address],
+    },
+    OpInfo {
+        opcode: 3,
+        mnemonic: "in",
+        args: &[Address],
+    },
+    OpInfo {
+        opcode: 4,
+        mnemonic: "out",
+        args: &[Value],
+    },
+    OpInfo {
+        opcode: 5,
+        mnemonic: "jnz",
+        args: &[Value, Value],
+    },
+    OpInfo {
+        opcode: 6,
+        mnemonic: "jz",
+        args: &[Value, Value],
+    },
+    OpInfo {
+        opcode: 7,
+        mnemonic: "lt",
+        args: &[Value, Value, Address],
+    },
+    OpInfo {
+        opcode: 8,
+        mnemonic: "eq",
+        args: &[Value, Value, Address],
+    },
+    OpInfo {
+        opcode: 9,
+        mnemonic: "arb",
+        args: &[Value],
+    },
+    OpInfo {
+        opcode: 99,
+        mnemonic: "halt",
+        args: &[],
+    },
+];
+
+pub const MAX_INSTRUCTION_LEN: usize = 4;
+
+pub fn op_info(op: Opcode) -> Option<&'static OpInfo> {
+    OPS.iter().find(|info| info.opcode == op)
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+pub mod ascii;
+pub mod asm;
+pub mod cfg;
+pub mod channel;
+pub mod compiler;
+pub mod custom_op;
+pub mod debugger;
+pub mod device;
+pub mod differential;
+pub mod disasm;
+mod error;
+pub mod golden;
+pub mod instruction;
+pub mod limits;
+mod machine;
+pub mod memory;
+pub mod packet;
+pub mod profile;
+pub mod repl;
+pub mod rng;
+mod snapshot;
+pub mod transpile;
+
+pub use error::IntcodeError;
+pub use machine::{Machine, State};
+pub use memory::{Memory, PagedMemory};
+
+pub type Prog = HashMap<usize, isize>;
+
+pub fn read_prog(prog_str: &str) -> Prog {
+    prog_str
+        .trim()
+        .split(',')
+        .enumerate()
+        .map(|(i, s)| (i, s.trim().parse().unwrap()))
+        .collect()
+}
+
+pub fn write_prog(prog: &Prog) -> String {
+    let len = prog.keys().max().map_or(0, |&max| max + 1);
+    (0..len)
+        .map(|i| prog.get(&i).unwrap_or(&0).to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+// Returning `None` from `input` fails the run with `IntcodeError::NoInput`.
+pub trait InputOutput {
+    fn input(&mut self) -> Option<isize>;
+    fn output(&mut self, o: isize);
+}
+
+// Inputs are consumed in order (FIFO), outputs are collected.
+#[derive(Debug, Default, Clone)]
+pub struct VecIO {
+    pub inputs: Vec<isize>,
+    pub outputs: Vec<isize>,
+}
+
+impl VecIO {
+    pub fn new(inputs: Vec<isize>) -> VecIO {
+        VecIO {
+            inputs,
+            outputs: vec![],
+        }
+    }
+}
+
+impl InputOutput for VecIO {
+    fn input(&mut self) -> Option<isize> {
+        if self.inputs.is_empty() {
+            return None;
+        }
+        Some(self.inputs.remove(0))
+    }
+    fn output(&mut self, o: isize) {
+        self.outputs.push(o);
+    }
+}
@@ -0,0 +1,265 @@
+// Running with an instruction budget, a wall-clock limit and loop detection,
+// for programs (or drivers) that might never stop.
+//
+// The loop detector checks the machine's whole state (pc, relative base,
+// memory and queued inputs) every `loop_check_interval` instructions. Seeing
+// a state again proves the machine will repeat itself forever, unless it asks
+// for input in between, which starts the detector over. Custom ops' handlers
+// and devices keep state of their own that the detector can't see, so it's off
+// for machines with either.
+
+use crate::{InputOutput, IntcodeError, Machine, Memory, State};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    pub max_instructions: Option<u64>,
+    pub max_time: Option<Duration>,
+    pub loop_check_interval: Option<u64>,
+}
+
+// Why a limited run stopped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Halted,
+    // The machine asked for input and none was given
+    NeedsInput,
+    OutOfInstructions,
+    OutOfTime,
+    // Back in a state it was in before, at this pc
+    Looping { pc: usize },
+}
+
+// The clock is only read every this many instructions
+const TIME_CHECK_INTERVAL: u64 = 1024;
+
+type Snapshot = (usize, usize, Vec<(usize, isize)>, Vec<isize>);
+
+// Keeps one state to compare against, saved at checks 1, 2, 4, 8, ... so a
+// loop of any length is eventually caught (Brent's cycle detection).
+struct LoopDetector {
+    saved: Option<(u64, Snapshot)>,
+    checks: u64,
+    next_save: u64,
+}
+
+impl LoopDetector {
+    fn new() -> LoopDetector {
+        LoopDetector {
+            saved: None,
+            checks: 0,
+            next_save: 1,
+        }
+    }
+
+    fn repeated<M: Memory>(&mut self, machine: &Machine<M>) -> bool {
+        let snapshot: Snapshot = (
+            machine.pc,
+            machine.base,
+            machine.memory().words(),
+            machine.pending_inputs().cloned().collect(),
+        );
+        let mut hasher = DefaultHasher::new();
+        snapshot.hash(&mut hasher);
+        let hash = hasher.finish();
+        if let Some((saved_hash, saved)) = &self.saved {
+            if *saved_hash == hash && *saved == snapshot {
+                return true;
+            }
+        }
+        self.checks += 1;
+        if self.checks == self.next_save {
+            self.saved = Some((hash, snapshot));
+            self.next_save *= 2;
+        }
+        false
+    }
+}
+
+impl<M: Memory> Machine<M> {
+    // Like `run`, but stops when a limit is reached, and when the machine
+    // needs input that `io` doesn't have rather than failing.
+    pub fn run_limited(
+        &mut self,
+        io: &mut impl InputOutput,
+        limits: &Limits,
+    ) -> Result<Outcome, IntcodeError> {
+        let start = Instant::now();
+        let mut detector = LoopDetector::new();
+        let mut executed = 0;
+        // An interval of 0 never checks
+        let loop_check_interval = limits
+            .loop_check_interval
+            .filter(|&interval| interval > 0 && !self.has_hidden_state());
+        loop {
+            if limits.max_instructions.is_some_and(|max| executed >= max) {
+                return Ok(Outcome::OutOfInstructions);
+            }
+            match self.step()? {
+                None => (),
+                Some(State::Output(o)) => io.output(o),
+                Some(State::NeedsInput) => match io.input() {
+                    Some(input) => {
+                        self.push_input(input);
+                        detector = LoopDetector::new();
+                        continue;
+                    }
+                    None => return Ok(Outcome::NeedsInput),
+                },
+                Some(State::Halted) => return Ok(Outcome::Halted),
+            }
+            executed += 1;
+            if let Some(max_time) = limits.max_time {
+                if executed % TIME_CHECK_INTERVAL == 0 && start.elapsed() >= max_time {
+                    return Ok(Outcome::OutOfTime);
+                }
+            }
+            if let Some(interval) = loop_check_interval {
+                if executed % interval == 0 && detector.repeated(self) {
+                    return Ok(Outcome::Looping { pc: self.pc });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::custom_op::Effect;
+    use crate::device::Timer;
+    use crate::{read_prog, VecIO};
+
+    fn run(prog: &str, inputs: Vec<isize>, limits: Limits) -> (Outcome, Vec<isize>) {
+        let mut io = VecIO::new(inputs);
+        let outcome = Machine::new(assemble(prog).unwrap())
+            .run_limited(&mut io, &limits)
+            .unwrap();
+        (outcome, io.outputs)
+    }
+
+    fn detect_loops() -> Limits {
+        Limits {
+            max_instructions: Some(10_000),
+            loop_check_interval: Some(7),
+            ..Limits::default()
+        }
+    }
+
+    #[test]
+    fn halts_or_needs_input() {
+        let echo = "in [0]\nout [0]\nhalt";
+        assert_eq!(
+            run(echo, vec![5], detect_loops()),
+            (Outcome::Halted, vec![5])
+        );
+        assert_eq!(
+            run(echo, vec![], detect_loops()),
+            (Outcome::NeedsInput, vec![])
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let count = "loop: add [n], #1, [n]\njz #0, #loop\nn: data 0";
+        let budget = Limits {
+            max_instructions: Some(100),
+            ..Limits::default()
+        };
+        let mut m = Machine::new(assemble(count).unwrap());
+        assert_eq!(
+            m.run_limited(&mut VecIO::default(), &budget).unwrap(),
+            Outcome::OutOfInstructions
+        );
+        assert_eq!(m.load(7), 50);
+
+        // Counting never repeats a state
+        assert_eq!(
+            run(count, vec![], detect_loops()).0,
+            Outcome::OutOfInstructions
+        );
+
+        let no_time = Limits {
+            max_time: Some(Duration::from_secs(0)),
+            ..Limits::default()
+        };
+        assert_eq!(run(count, vec![], no_time).0, Outcome::OutOfTime);
+
+        let never_check = Limits {
+            loop_check_interval: Some(0),
+            ..budget
+        };
+        assert_eq!(
+            run(count, vec![], never_check).0,
+            Outcome::OutOfInstructions
+        );
+    }
+
+    #[test]
+    fn loops() {
+        // Flips a word between 0 and 1 forever, outputting it each time
+        let flip = "
+            loop:   eq [x], #0, [x]
+                    out [x]
+                    jz #0, #loop
+            x:      data 0
+            ";
+        let (outcome, outputs) = run(flip, vec![], detect_loops());
+        assert!(matches!(outcome, Outcome::Looping { .. }));
+        assert!(outputs.starts_with(&[1, 0, 1, 0]));
+
+        // Reading input starts the detector over, so this runs until it's out
+        let echo_forever = read_prog("3,7,4,7,1105,1,0");
+        let mut io = VecIO::new(vec![1; 10]);
+        assert_eq!(
+            Machine::new(echo_forever)
+                .run_limited(&mut io, &detect_loops())
+                .unwrap(),
+            Outcome::NeedsInput
+        );
+        assert_eq!(io.outputs, vec![1; 10]);
+
+        // Looks like a loop, but the custom op breaks out of it eventually
+        let mut m = Machine::new(read_prog("42,1106,0,0,99"));
+        let mut calls = 0;
+        m.register_op(42, "countdown", &[], move |_| {
+            calls += 1;
+            if calls == 20 {
+                Effect::Jump(4)
+            } else {
+                Effect::Continue
+            }
+        });
+        assert_eq!(
+            m.run_limited(&mut VecIO::default(), &detect_loops())
+                .unwrap(),
+            Outcome::Halted
+        );
+
+        // Same for a timer counting up to 50
+        let mut m = Machine::new(
+            assemble(
+                "
+                        add #0, #0, [1000]
+                loop:   lt [1000], #50, [t]
+                        jnz [t], #loop
+                        out #1
+                        halt
+                t:      data 0
+                ",
+            )
+            .unwrap(),
+        );
+        m.attach(1000, Timer::default());
+        let mut io = VecIO::default();
+        let limits = Limits {
+            loop_check_interval: Some(2),
+            ..detect_loops()
+        };
+        assert_eq!(m.run_limited(&mut io, &limits).unwrap(), Outcome::Halted);
+        assert_eq!(io.outputs, vec![1]);
+    }
+}
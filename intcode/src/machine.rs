@@ -0,0 +1,752 @@
+use crate::custom_op::{CustomOp, Effect};
+use crate::device::{Attached, Device};
+use crate::error::{Fault, IntcodeError};
+use crate::instruction::{
+    op_info, opcode, param_mode, ArgType, Opcode, ParamMode, MAX_INSTRUCTION_LEN,
+};
+use crate::memory::Memory;
+use crate::profile::Profile;
+use crate::{InputOutput, Prog, VecIO};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone)]
+pub struct Machine<M = Prog> {
+    memory: M,
+    pub pc: usize,
+    pub base: usize,
+    inputs: VecDeque<isize>,
+    decode_cache: Option<DecodeCache>,
+    checked_arithmetic: bool,
+    profile: Option<Box<Profile>>,
+    custom_ops: Vec<CustomOp>,
+    devices: Vec<Attached>,
+    // Every write instructions have made since tracking started, when it's on
+    writes: Option<Vec<(usize, isize)>>,
+}
+
+// Why `resume` stopped. On `NeedsInput` the pc is left on the input
+// instruction, so pushing an input and resuming retries it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+    NeedsInput,
+    Output(isize),
+    Halted,
+}
+
+impl Machine {
+    pub fn run_once(prog: &Prog, inputs: Vec<isize>) -> Result<Vec<isize>, IntcodeError> {
+        let mut io = VecIO::new(inputs);
+        Machine::new(prog.to_owned()).run(&mut io)?;
+        Ok(io.outputs)
+    }
+}
+
+// An instruction with its opcode and parameter modes parsed, before any
+// parameters are resolved against memory or the relative base.
+#[derive(Debug, Clone, Copy)]
+struct Decoded {
+    opcode: Opcode,
+    len: usize,
+    modes: [ParamMode; 3],
+    params: [isize; 3],
+}
+
+// Decoded instructions by pc: dense over the program the machine was loaded
+// with, and sparse past it, so jumping far away doesn't allocate a cache all
+// the way out there.
+#[derive(Debug, Clone, Default)]
+struct DecodeCache {
+    near: Vec<Option<Decoded>>,
+    far: HashMap<usize, Decoded>,
+}
+
+impl DecodeCache {
+    fn new(memory: &impl Memory) -> DecodeCache {
+        let len = memory.words().last().map_or(0, |&(address, _)| address + 1);
+        DecodeCache {
+            near: vec![None; len],
+            far: HashMap::new(),
+        }
+    }
+
+    fn get(&self, pc: usize) -> Option<Decoded> {
+        match self.near.get(pc) {
+            Some(decoded) => *decoded,
+            None => self.far.get(&pc).copied(),
+        }
+    }
+
+    fn insert(&mut self, pc: usize, decoded: Decoded) {
+        match self.near.get_mut(pc) {
+            Some(slot) => *slot = Some(decoded),
+            None => {
+                self.far.insert(pc, decoded);
+            }
+        }
+    }
+
+    // Forgets any instruction overlapping `address`
+    fn invalidate(&mut self, address: usize) {
+        for pc in address.saturating_sub(MAX_INSTRUCTION_LEN - 1)..=address {
+            match self.near.get_mut(pc) {
+                Some(slot) => *slot = None,
+                None => {
+                    self.far.remove(&pc);
+                }
+            }
+        }
+    }
+}
+
+impl<M: Memory> Machine<M> {
+    pub fn new(memory: M) -> Machine<M> {
+        let decode_cache = Some(DecodeCache::new(&memory));
+        Machine {
+            memory,
+            pc: 0,
+            base: 0,
+            inputs: VecDeque::new(),
+            decode_cache,
+            checked_arithmetic: true,
+            profile: None,
+            custom_ops: vec![],
+            devices: vec![],
+            writes: None,
+        }
+    }
+
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    // The decode cache is on by default, turning it off decodes every
+    // instruction every time it is executed.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decode_cache = if enabled {
+            Some(DecodeCache::new(&self.memory))
+        } else {
+            None
+        };
+    }
+
+    pub fn decode_cache_enabled(&self) -> bool {
+        self.decode_cache.is_some()
+    }
+
+    // Arithmetic is checked by default, failing with `IntcodeError::Overflow`
+    // when an add or multiply doesn't fit. Turning it off wraps instead.
+    pub fn set_checked_arithmetic(&mut self, enabled: bool) {
+        self.checked_arithmetic = enabled;
+    }
+
+    pub fn checked_arithmetic(&self) -> bool {
+        self.checked_arithmetic
+    }
+
+    // Profiling is off by default. Turning it on (again) starts a new profile.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = if enabled { Some(Box::default()) } else { None };
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    // Adds an instruction to the machine's ISA. Panics if the opcode isn't
+    // free (1-98 and not built-in) or there are more args than fit in an
+    // instruction.
+    pub fn register_op<F>(
+        &mut self,
+        opcode: Opcode,
+        mnemonic: &'static str,
+        args: &[ArgType],
+        handler: F,
+    ) where
+        F: FnMut(&[isize]) -> Effect + Send + 'static,
+    {
+        assert!(
+            (1..99).contains(&opcode)
+                && op_info(opcode).is_none()
+                && self.custom_op(opcode).is_none(),
+            "Opcode {} is not free",
+            opcode
+        );
+        assert!(
+            args.len() < MAX_INSTRUCTION_LEN,
+            "Too many args for opcode {}",
+            opcode
+        );
+        self.custom_ops.push(CustomOp {
+            opcode,
+            mnemonic,
+            args: args.to_vec(),
+            handler: Arc::new(Mutex::new(handler)),
+        });
+    }
+
+    pub fn custom_op(&self, opcode: Opcode) -> Option<&CustomOp> {
+        self.custom_ops.iter().find(|op| op.opcode == opcode)
+    }
+
+    // Whether custom ops or devices could be keeping state outside the
+    // machine's own
+    // Starts tracking the writes instructions make, for `take_writes`
+    pub(crate) fn track_writes(&mut self) {
+        self.writes.get_or_insert_with(Vec::new);
+    }
+
+    // The addresses and values written since the last call
+    pub(crate) fn take_writes(&mut self) -> Vec<(usize, isize)> {
+        self.writes.as_mut().map_or(vec![], std::mem::take)
+    }
+
+    pub(crate) fn has_hidden_state(&self) -> bool {
+        !self.custom_ops.is_empty() || !self.devices.is_empty()
+    }
+
+    // Maps `device` to its `len` words from `start`. Returns a handle for
+    // looking at (or changing) the device while the machine runs. Panics if
+    // the range overlaps another device's or runs past the last address.
+    pub fn attach<D: Device + 'static>(&mut self, start: usize, device: D) -> Arc<Mutex<D>> {
+        let len = device.len();
+        let end = start
+            .checked_add(len)
+            .unwrap_or_else(|| panic!("Device at {} runs past the last address", start));
+        assert!(
+            self.devices
+                .iter()
+                .all(|d| end <= d.start || d.start + d.len <= start),
+            "Device at {}..{} overlaps another",
+            start,
+            end
+        );
+        let device = Arc::new(Mutex::new(device));
+        self.devices.push(Attached {
+            start,
+            len,
+            device: device.clone(),
+        });
+        device
+    }
+
+    pub fn push_input(&mut self, input: isize) {
+        self.inputs.push_back(input);
+    }
+
+    pub fn pending_inputs(&self) -> impl Iterator<Item = &isize> {
+        self.inputs.iter()
+    }
+
+    pub fn load(&self, address: usize) -> isize {
+        self.memory.load(address)
+    }
+
+    pub fn store(&mut self, address: usize, value: isize) {
+        if let Some(cache) = &mut self.decode_cache {
+            cache.invalidate(address);
+        }
+        self.memory.store(address, value);
+    }
+
+    fn address(address: isize) -> Result<usize, Fault> {
+        if address < 0 {
+            return Err(Fault::NegativeAddress(address));
+        }
+        Ok(address as usize)
+    }
+
+    fn relative_to_base_address(&self, offset: isize) -> Result<usize, Fault> {
+        Self::address(
+            (self.base as isize)
+                .checked_add(offset)
+                .ok_or(Fault::Overflow)?,
+        )
+    }
+
+    fn decode(&self, pc: usize) -> Result<Decoded, Fault> {
+        let raw = self.load(pc);
+        let op = opcode(raw);
+        let args = match (op_info(op), self.custom_op(op)) {
+            (Some(info), _) => info.args,
+            (None, Some(custom)) => &custom.args[..],
+            (None, None) => return Err(Fault::UnknownOpcode),
+        };
+        let mut decoded = Decoded {
+            opcode: op,
+            len: 1 + args.len(),
+            modes: [ParamMode::Immediate; 3],
+            params: [0; 3],
+        };
+        for (i, arg_type) in args.iter().enumerate() {
+            decoded.modes[i] = param_mode(raw, i, arg_type).ok_or(Fault::InvalidParamMode(i))?;
+            decoded.params[i] = self.load(pc + 1 + i);
+        }
+        Ok(decoded)
+    }
+
+    fn fetch(&mut self) -> Result<Decoded, Fault> {
+        let pc = self.pc;
+        match &self.decode_cache {
+            Some(cache) => {
+                if let Some(decoded) = cache.get(pc) {
+                    return Ok(decoded);
+                }
+            }
+            None => return self.decode(pc),
+        }
+        let decoded = self.decode(pc)?;
+        self.decode_cache.as_mut().unwrap().insert(pc, decoded);
+        Ok(decoded)
+    }
+
+    // Loads and stores made by instructions, as opposed to the machine's
+    // driver.
+    fn read(&mut self, address: usize) -> isize {
+        if let Some(profile) = &mut self.profile {
+            profile.read(address);
+        }
+        for d in &self.devices {
+            if let Some(offset) = d.offset(address) {
+                return d.device.lock().unwrap().read(offset);
+            }
+        }
+        self.load(address)
+    }
+
+    fn write(&mut self, address: usize, value: isize) {
+        if let Some(profile) = &mut self.profile {
+            profile.write(address);
+        }
+        if let Some(writes) = &mut self.writes {
+            writes.push((address, value));
+        }
+        for d in &self.devices {
+            if let Some(offset) = d.offset(address) {
+                return d.device.lock().unwrap().write(offset, value);
+            }
+        }
+        self.store(address, value);
+    }
+
+    // Resolves the i-th parameter to a value, or an address for params
+    // written to.
+    fn arg(&mut self, d: &Decoded, i: usize) -> Result<isize, Fault> {
+        Ok(match d.modes[i] {
+            ParamMode::Position => self.read(Self::address(d.params[i])?),
+            ParamMode::Immediate => d.params[i],
+            ParamMode::RelativeToBaseValue => {
+                self.read(self.relative_to_base_address(d.params[i])?)
+            }
+            ParamMode::RelativeToBaseAddress => {
+                self.relative_to_base_address(d.params[i])? as isize
+            }
+        })
+    }
+
+    fn op_load_reduce_store<F>(&mut self, d: &Decoded, reduce: F) -> Result<(), Fault>
+    where
+        F: FnOnce(isize, isize) -> Option<isize>,
+    {
+        let v = reduce(self.arg(d, 0)?, self.arg(d, 1)?).ok_or(Fault::Overflow)?;
+        let address = Self::address(self.arg(d, 2)?)?;
+        self.write(address, v);
+        Ok(())
+    }
+
+    fn op_input(&mut self, d: &Decoded, input: isize) -> Result<(), Fault> {
+        let address = Self::address(self.arg(d, 0)?)?;
+        self.write(address, input);
+        Ok(())
+    }
+
+    // Returns whether the jump was taken
+    fn op_jump_if<F>(&mut self, d: &Decoded, f: F) -> Result<bool, Fault>
+    where
+        F: FnOnce(isize) -> bool,
+    {
+        if !f(self.arg(d, 0)?) {
+            return Ok(false);
+        }
+        let target = self.arg(d, 1)?;
+        if target < 0 {
+            return Err(Fault::NegativeJump(target));
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.jump(self.pc, target as usize);
+        }
+        self.pc = target as usize;
+        Ok(true)
+    }
+
+    fn op_adjust_relative_base(&mut self, d: &Decoded) -> Result<(), Fault> {
+        let delta = self.arg(d, 0)?;
+        let new_base = (self.base as isize)
+            .checked_add(delta)
+            .ok_or(Fault::Overflow)?;
+        if new_base < 0 {
+            return Err(Fault::NegativeBase(new_base));
+        }
+        self.base = new_base as usize;
+        Ok(())
+    }
+
+    fn op_custom(&mut self, d: &Decoded) -> Result<Effect, Fault> {
+        // Copied out of the op, as cloning it would allocate its args
+        let op = self.custom_op(d.opcode).unwrap();
+        let count = op.args.len();
+        let mut arg_types = [ArgType::Value; 3];
+        arg_types[..count].copy_from_slice(&op.args);
+        let handler = op.handler.clone();
+        let mut args = [0; 3];
+        for (i, arg_type) in arg_types[..count].iter().enumerate() {
+            args[i] = self.arg(d, i)?;
+            if *arg_type == ArgType::Address {
+                Self::address(args[i])?;
+            }
+        }
+        let effect = (handler.lock().unwrap())(&args[..count]);
+        if let Effect::Store(address, value) = effect {
+            self.write(address, value);
+        }
+        Ok(effect)
+    }
+
+    fn execute(&mut self) -> Result<Option<State>, Fault> {
+        let d = self.fetch()?;
+        if let Some(profile) = &mut self.profile {
+            // Waiting for input doesn't count as executing
+            if d.opcode != 3 || !self.inputs.is_empty() {
+                profile.instruction(self.pc, d.opcode);
+            }
+        }
+        let checked = self.checked_arithmetic;
+        let mut state = None;
+        match d.opcode {
+            1 => self.op_load_reduce_store(&d, |a, b| {
+                if checked {
+                    a.checked_add(b)
+                } else {
+                    Some(a.wrapping_add(b))
+                }
+            })?,
+            2 => self.op_load_reduce_store(&d, |a, b| {
+                if checked {
+                    a.checked_mul(b)
+                } else {
+                    Some(a.wrapping_mul(b))
+                }
+            })?,
+            3 => match self.inputs.pop_front() {
+                Some(input) => self.op_input(&d, input)?,
+                None => return Ok(Some(State::NeedsInput)),
+            },
+            4 => state = Some(State::Output(self.arg(&d, 0)?)),
+            5 => {
+                if self.op_jump_if(&d, |v| v != 0)? {
+                    return Ok(None);
+                }
+            }
+            6 => {
+                if self.op_jump_if(&d, |v| v == 0)? {
+                    return Ok(None);
+                }
+            }
+            7 => self.op_load_reduce_store(&d, |a, b| Some(if a < b { 1 } else { 0 }))?,
+            8 => self.op_load_reduce_store(&d, |a, b| Some(if a == b { 1 } else { 0 }))?,
+            9 => self.op_adjust_relative_base(&d)?,
+            99 => return Ok(Some(State::Halted)),
+            _ => match self.op_custom(&d)? {
+                Effect::Jump(target) => {
+                    self.pc = target;
+                    return Ok(None);
+                }
+                Effect::Output(o) => state = Some(State::Output(o)),
+                Effect::Continue | Effect::Store(..) => (),
+            },
+        };
+        self.pc += d.len;
+        Ok(state)
+    }
+
+    // Executes a single instruction. Returns the state if it stopped the
+    // machine (an output also completes the instruction).
+    pub fn step(&mut self) -> Result<Option<State>, IntcodeError> {
+        let pc = self.pc;
+        let state = self.execute().map_err(|fault| {
+            self.pc = pc;
+            fault.at(pc, self.load(pc))
+        })?;
+        if state != Some(State::NeedsInput) {
+            for d in &self.devices {
+                d.device.lock().unwrap().tick();
+            }
+        }
+        Ok(state)
+    }
+
+    pub fn resume(&mut self) -> Result<State, IntcodeError> {
+        loop {
+            if let Some(state) = self.step()? {
+                return Ok(state);
+            }
+        }
+    }
+
+    // For drivers that know the program is about to output (ex. the rest of a
+    // multi-value output). An error if the machine stops for any other reason.
+    pub fn expect_output(&mut self) -> Result<isize, IntcodeError> {
+        match self.resume()? {
+            State::Output(o) => Ok(o),
+            state => Err(IntcodeError::UnexpectedState {
+                pc: self.pc,
+                raw: self.load(self.pc),
+                state,
+            }),
+        }
+    }
+
+    pub fn run(&mut self, io: &mut impl InputOutput) -> Result<(), IntcodeError> {
+        loop {
+            match self.resume()? {
+                State::NeedsInput => match io.input() {
+                    Some(input) => self.push_input(input),
+                    None => return Err(Fault::NoInput.at(self.pc, self.load(self.pc))),
+                },
+                State::Output(o) => io.output(o),
+                State::Halted => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::memory::PagedMemory;
+    use crate::read_prog;
+
+    fn prog_string(prog: &[isize]) -> String {
+        prog.iter()
+            .map(isize::to_string)
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    #[test]
+    fn quine() {
+        // A program that output's itself...
+        let prog = [
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        assert_eq!(
+            Machine::run_once(&read_prog(&prog_string(&prog)), vec![]).unwrap(),
+            prog
+        );
+    }
+
+    #[test]
+    fn large_numbers() {
+        assert_eq!(
+            Machine::run_once(&read_prog("1102,34915192,34915192,7,4,7,99,0"), vec![]).unwrap(),
+            vec![1219070632396864]
+        );
+        assert_eq!(
+            Machine::run_once(&read_prog("104,1125899906842624,99"), vec![]).unwrap(),
+            vec![1125899906842624]
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let square = read_prog("2,7,7,7,4,7,99,4294967296");
+        assert_eq!(
+            Machine::run_once(&square, vec![]).unwrap_err(),
+            IntcodeError::Overflow { pc: 0, raw: 2 }
+        );
+
+        let mut m = Machine::new(square);
+        m.set_checked_arithmetic(false);
+        assert_eq!(m.resume().unwrap(), State::Output(0));
+
+        assert_eq!(
+            Machine::run_once(&read_prog("109,1,209,9223372036854775807,99"), vec![])
+                .unwrap_err()
+                .to_string(),
+            "arithmetic overflow (instruction 209 at pc 2)"
+        );
+    }
+
+    #[test]
+    fn compare_to_8() {
+        let equals_8 = read_prog("3,9,8,9,10,9,4,9,99,-1,8");
+        assert_eq!(Machine::run_once(&equals_8, vec![8]).unwrap(), vec![1]);
+        assert_eq!(Machine::run_once(&equals_8, vec![7]).unwrap(), vec![0]);
+
+        let less_than_8_imm = read_prog("3,3,1107,-1,8,3,4,3,99");
+        assert_eq!(
+            Machine::run_once(&less_than_8_imm, vec![7]).unwrap(),
+            vec![1]
+        );
+        assert_eq!(
+            Machine::run_once(&less_than_8_imm, vec![9]).unwrap(),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn inputs_are_fifo() {
+        let echo_twice = read_prog("3,0,4,0,3,0,4,0,99");
+        assert_eq!(
+            Machine::run_once(&echo_twice, vec![1, 2]).unwrap(),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let run = |prog: &str, inputs| Machine::run_once(&read_prog(prog), inputs).unwrap_err();
+        assert_eq!(
+            run("104,1,42", vec![]),
+            IntcodeError::UnknownOpcode { pc: 2, raw: 42 }
+        );
+        assert_eq!(
+            run("3101,0,0,0,99", vec![]),
+            IntcodeError::InvalidParamMode {
+                pc: 0,
+                raw: 3101,
+                param: 1
+            }
+        );
+        assert_eq!(
+            run("4,-5,99", vec![]),
+            IntcodeError::NegativeAddress {
+                pc: 0,
+                raw: 4,
+                address: -5
+            }
+        );
+        assert_eq!(
+            run("1105,1,-1", vec![]),
+            IntcodeError::NegativeJump {
+                pc: 0,
+                raw: 1105,
+                target: -1
+            }
+        );
+        assert_eq!(
+            run("109,2,109,-3,99", vec![]),
+            IntcodeError::NegativeBase {
+                pc: 2,
+                raw: 109,
+                base: -1
+            }
+        );
+        assert_eq!(
+            run("3,0,3,0,99", vec![1]),
+            IntcodeError::NoInput { pc: 2, raw: 3 }
+        );
+        assert_eq!(
+            run("104,1,42", vec![]).to_string(),
+            "unknown opcode (instruction 42 at pc 2)"
+        );
+
+        let mut m = Machine::new(read_prog("104,1,99"));
+        assert_eq!(m.expect_output(), Ok(1));
+        let halted = m.expect_output().unwrap_err();
+        assert_eq!(
+            halted,
+            IntcodeError::UnexpectedState {
+                pc: 2,
+                raw: 99,
+                state: State::Halted
+            }
+        );
+        assert_eq!(
+            halted.to_string(),
+            "expected output, but got Halted (instruction 99 at pc 2)"
+        );
+    }
+
+    #[test]
+    fn self_modifying_code_invalidates_decode_cache() {
+        let prog = assemble(
+            "
+            start:  out #5
+                    add [start+1], #1, [start+1]
+                    add [n], #1, [n]
+                    lt [n], #2, [t]
+                    jnz [t], #start
+                    halt
+            n:      data 0
+            t:      data 0
+            ",
+        )
+        .unwrap();
+        for cached in &[true, false] {
+            let mut m = Machine::new(prog.clone());
+            m.set_decode_cache(*cached);
+            let mut io = VecIO::default();
+            m.run(&mut io).unwrap();
+            assert_eq!(io.outputs, vec![5, 6]);
+        }
+
+        // Code far past the program is cached sparsely, and invalidated too
+        let mut m = Machine::new(read_prog("1105,1,500000"));
+        m.store(500_000, 104);
+        m.store(500_001, 5);
+        assert_eq!(m.resume().unwrap(), State::Output(5));
+        m.store(500_001, 6);
+        m.pc = 500_000;
+        assert_eq!(m.resume().unwrap(), State::Output(6));
+        let cache = m.decode_cache.as_ref().unwrap();
+        assert_eq!(cache.near.len(), 3);
+        assert!(cache.far.contains_key(&500_000));
+    }
+
+    #[test]
+    fn custom_ops() {
+        let mut m = Machine::new(read_prog("42,20,4,20,143,21,144,10,99,0,242,0,4,0,55"));
+        let mut next_rand = 1000;
+        m.register_op(42, "rand", &[ArgType::Address], move |args| {
+            next_rand += 1;
+            Effect::Store(args[0] as usize, next_rand - 1)
+        });
+        m.register_op(43, "dbl", &[ArgType::Value], |args| {
+            Effect::Output(args[0] * 2)
+        });
+        m.register_op(44, "goto", &[ArgType::Value], |args| {
+            Effect::Jump(args[0] as usize)
+        });
+        assert_eq!(m.custom_op(43).unwrap().mnemonic, "dbl");
+
+        let mut io = VecIO::default();
+        assert_eq!(
+            m.run(&mut io).unwrap_err(),
+            IntcodeError::UnknownOpcode { pc: 14, raw: 55 }
+        );
+        assert_eq!(io.outputs, vec![1000, 42, 1001]);
+    }
+
+    #[test]
+    fn paged_memory() {
+        let prog = read_prog("1102,34915192,34915192,7,4,7,99,0,4,2000,99");
+        let mut m = Machine::new(PagedMemory::from(&prog));
+        assert_eq!(m.resume().unwrap(), State::Output(1219070632396864));
+        m.pc = 8;
+        assert_eq!(m.resume().unwrap(), State::Output(0));
+    }
+
+    #[test]
+    fn resume() {
+        let mut m = Machine::new(read_prog("104,1,3,0,4,0,99"));
+        assert_eq!(m.resume().unwrap(), State::Output(1));
+        assert_eq!(m.resume().unwrap(), State::NeedsInput);
+        assert_eq!(m.resume().unwrap(), State::NeedsInput);
+        m.push_input(7);
+        assert_eq!(m.resume().unwrap(), State::Output(7));
+        assert_eq!(m.resume().unwrap(), State::Halted);
+        assert_eq!(m.resume().unwrap(), State::Halted);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::Prog;
+
+// Backing store for a machine. Addresses that were never stored to read as 0.
+pub trait Memory {
+    fn load(&self, address: usize) -> isize;
+    fn store(&mut self, address: usize, value: isize);
+    // Every non-zero word, ordered by address
+    fn words(&self) -> Vec<(usize, isize)>;
+}
+
+// Sparse, good for small programs poking at far away addresses.
+impl Memory for Prog {
+    fn load(&self, address: usize) -> isize {
+        *self.get(&address).unwrap_or(&0)
+    }
+
+    fn store(&mut self, address: usize, value: isize) {
+        self.insert(address, value);
+    }
+
+    fn words(&self) -> Vec<(usize, isize)> {
+        let mut words: Vec<(usize, isize)> = self
+            .iter()
+            .filter(|(_, &v)| v != 0)
+            .map(|(&a, &v)| (a, v))
+            .collect();
+        words.sort();
+        words
+    }
+}
+
+const PAGE_BITS: usize = 10;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+// Addresses past the dense pages (64M words) fall back to sparse storage, so a
+// stray store to a huge address doesn't allocate a huge page table.
+const MAX_PAGES: usize = 1 << 16;
+
+type Page = Box<[isize; PAGE_SIZE]>;
+
+// Dense, allocated a zero-filled page at a time as addresses are stored to.
+#[derive(Debug, Clone, Default)]
+pub struct PagedMemory {
+    pages: Vec<Option<Page>>,
+    far: Prog,
+}
+
+impl PagedMemory {
+    pub fn new() -> PagedMemory {
+        PagedMemory::default()
+    }
+}
+
+impl From<&Prog> for PagedMemory {
+    fn from(prog: &Prog) -> PagedMemory {
+        let mut memory = PagedMemory::new();
+        for (&address, &value) in prog {
+            memory.store(address, value);
+        }
+        memory
+    }
+}
+
+impl Memory for PagedMemory {
+    fn load(&self, address: usize) -> isize {
+        match self.pages.get(address >> PAGE_BITS) {
+            Some(Some(page)) => page[address & (PAGE_SIZE - 1)],
+            Some(None) => 0,
+            None => self.far.load(address),
+        }
+    }
+
+    fn store(&mut self, address: usize, value: isize) {
+        let page_index = address >> PAGE_BITS;
+        if page_index >= MAX_PAGES {
+            return self.far.store(address, value);
+        }
+        if page_index >= self.pages.len() {
+            self.pages.resize_with(page_index + 1, || None);
+        }
+        let page = self.pages[page_index].get_or_insert_with(|| Box::new([0; PAGE_SIZE]));
+        page[address & (PAGE_SIZE - 1)] = value;
+    }
+
+    fn words(&self) -> Vec<(usize, isize)> {
+        let mut words: Vec<(usize, isize)> = self
+            .pages
+            .iter()
+            .enumerate()
+            .filter_map(|(i, page)| Some((i << PAGE_BITS, page.as_ref()?)))
+            .flat_map(|(start, page)| page.iter().enumerate().map(move |(i, &v)| (start + i, v)))
+            .filter(|&(_, v)| v != 0)
+            .collect();
+        words.extend(self.far.words());
+        words
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_prog;
+
+    #[test]
+    fn paged_memory() {
+        let mut memory = PagedMemory::from(&read_prog("1,2,3"));
+        assert_eq!(memory.load(2), 3);
+        assert_eq!(memory.load(3), 0);
+        assert_eq!(memory.load(1 << 40), 0);
+
+        memory.store(5000, 7);
+        assert_eq!(memory.load(5000), 7);
+        assert_eq!(memory.load(4999), 0);
+        assert_eq!(memory.pages.iter().filter(|p| p.is_some()).count(), 2);
+
+        memory.store(1 << 40, 9);
+        assert_eq!(memory.load(1 << 40), 9);
+        assert_eq!(memory.pages.len(), 5);
+
+        memory.store(1, 0);
+        assert_eq!(
+            memory.words(),
+            vec![(0, 1), (2, 3), (5000, 7), (1 << 40, 9)]
+        );
+    }
+}
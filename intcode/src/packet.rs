@@ -0,0 +1,378 @@
+// Runs machines that talk in packets: each machine is booted with its
+// address as its first input, then sends `(dest, x, y)` as three outputs and
+// receives packets as two inputs, `x` then `y`. A machine asking for input
+// with no packet queued gets `no_packet` instead.
+//
+// Machines take turns, in address order. On its turn a machine runs until it
+// has been given one input (a packet or `no_packet`) and asks for another, or
+// until it has used up `turn_budget` instructions (a packet can be split
+// across turns). Packets are routed as soon as each turn ends, so a packet can
+// make it around the whole network in a single round. Halting or asking for
+// input partway through sending a packet is an error.
+
+use crate::{IntcodeError, Machine, Memory, Prog, State};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Packet {
+    pub source: isize,
+    pub dest: isize,
+    pub x: isize,
+    pub y: isize,
+}
+
+// Decides which machine a packet is queued for, given how many there are.
+// Returning `None` (or a machine past the last) drops the packet.
+pub trait Router {
+    fn route(&mut self, packet: &Packet, machines: usize) -> Option<usize>;
+}
+
+// Delivers to the machine at `dest`, dropping packets for other addresses.
+pub struct Direct;
+
+impl Router for Direct {
+    fn route(&mut self, packet: &Packet, machines: usize) -> Option<usize> {
+        if packet.dest >= 0 && (packet.dest as usize) < machines {
+            Some(packet.dest as usize)
+        } else {
+            None
+        }
+    }
+}
+
+impl<F: FnMut(&Packet, usize) -> Option<usize>> Router for F {
+    fn route(&mut self, packet: &Packet, machines: usize) -> Option<usize> {
+        self(packet, machines)
+    }
+}
+
+// What a monitor wants done about the traffic it has seen
+#[derive(Debug, Default)]
+pub struct Actions {
+    injected: Vec<Packet>,
+    stopped: bool,
+}
+
+impl Actions {
+    // Routes a packet as if a machine had sent it
+    pub fn inject(&mut self, packet: Packet) {
+        self.injected.push(packet);
+    }
+
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+}
+
+pub trait Monitor {
+    // Sees every packet a machine sends, before it is routed
+    fn sent(&mut self, _packet: &Packet, _actions: &mut Actions) {}
+    // Called after a round where nothing was sent or received
+    fn idle(&mut self, _actions: &mut Actions) {}
+}
+
+impl Monitor for () {}
+
+pub struct PacketNetwork<M = Prog, R = Direct> {
+    machines: Vec<Machine<M>>,
+    queues: Vec<VecDeque<(isize, isize)>>,
+    halted: Vec<bool>,
+    // Outputs of each machine's packet in progress
+    partial: Vec<Vec<isize>>,
+    router: R,
+    pub no_packet: isize,
+    pub turn_budget: u64,
+}
+
+impl<M: Memory, R: Router> PacketNetwork<M, R> {
+    pub fn boot(mut machines: Vec<Machine<M>>, router: R) -> PacketNetwork<M, R> {
+        for (address, machine) in machines.iter_mut().enumerate() {
+            machine.push_input(address as isize);
+        }
+        PacketNetwork {
+            queues: machines.iter().map(|_| VecDeque::new()).collect(),
+            halted: machines.iter().map(|_| false).collect(),
+            partial: machines.iter().map(|_| vec![]).collect(),
+            machines,
+            router,
+            no_packet: -1,
+            turn_budget: 100_000,
+        }
+    }
+
+    pub fn machines(&self) -> &[Machine<M>] {
+        &self.machines
+    }
+
+    // Packets waiting to be received, by machine
+    pub fn queued(&self, address: usize) -> impl Iterator<Item = &(isize, isize)> {
+        self.queues[address].iter()
+    }
+
+    pub fn inject(&mut self, packet: Packet) {
+        let machines = self.machines.len();
+        if let Some(to) = self
+            .router
+            .route(&packet, machines)
+            .filter(|&to| to < machines)
+        {
+            self.queues[to].push_back((packet.x, packet.y));
+        }
+    }
+
+    // Runs the machine's turn, returning the packets it sent and whether it
+    // did anything besides (received a packet, or ran out of budget).
+    fn turn(&mut self, address: usize) -> Result<(Vec<Packet>, bool), IntcodeError> {
+        let mut sent = vec![];
+        let mut given_input = false;
+        let mut received = false;
+        let machine = &mut self.machines[address];
+        let partial = &mut self.partial[address];
+        // A budget of 0 would never get anywhere
+        for _ in 0..self.turn_budget.max(1) {
+            let state = match machine.step()? {
+                Some(state) => state,
+                None => continue,
+            };
+            if !partial.is_empty() && !matches!(state, State::Output(_)) {
+                return Err(IntcodeError::UnexpectedState {
+                    pc: machine.pc,
+                    raw: machine.load(machine.pc),
+                    state,
+                });
+            }
+            match state {
+                State::Output(o) => {
+                    partial.push(o);
+                    if let [dest, x, y] = partial[..] {
+                        sent.push(Packet {
+                            source: address as isize,
+                            dest,
+                            x,
+                            y,
+                        });
+                        partial.clear();
+                    }
+                }
+                State::NeedsInput if given_input => return Ok((sent, received)),
+                State::NeedsInput => {
+                    given_input = true;
+                    match self.queues[address].pop_front() {
+                        Some((x, y)) => {
+                            machine.push_input(x);
+                            machine.push_input(y);
+                            received = true;
+                        }
+                        None => machine.push_input(self.no_packet),
+                    }
+                }
+                State::Halted => {
+                    self.halted[address] = true;
+                    return Ok((sent, received));
+                }
+            }
+        }
+        Ok((sent, true))
+    }
+
+    // Gives every machine that hasn't halted a turn. Returns whether the
+    // network should keep running: false once the monitor stops it or every
+    // machine has halted.
+    pub fn round(&mut self, monitor: &mut impl Monitor) -> Result<bool, IntcodeError> {
+        let mut actions = Actions::default();
+        let mut busy = false;
+        for address in 0..self.machines.len() {
+            if self.halted[address] {
+                continue;
+            }
+            let (sent, active) = self.turn(address)?;
+            busy |= active || !sent.is_empty();
+            for packet in sent {
+                monitor.sent(&packet, &mut actions);
+                if actions.stopped {
+                    return Ok(false);
+                }
+                self.inject(packet);
+            }
+            for packet in actions.injected.drain(..).collect::<Vec<Packet>>() {
+                self.inject(packet);
+            }
+        }
+        if !busy {
+            monitor.idle(&mut actions);
+            for packet in actions.injected.drain(..).collect::<Vec<Packet>>() {
+                self.inject(packet);
+            }
+        }
+        Ok(!actions.stopped && self.halted.iter().any(|&h| !h))
+    }
+
+    pub fn run(&mut self, monitor: &mut impl Monitor) -> Result<(), IntcodeError> {
+        while self.round(monitor)? {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    // Address 0 starts by sending (1, 7, 0). Every machine forwards what it
+    // receives to the next address, adding 1 to y.
+    fn forwarder() -> Prog {
+        assemble(
+            "
+                    in [addr]
+                    jnz [addr], #loop
+                    out #1
+                    out #7
+                    out #0
+            loop:   in [x]
+                    eq [x], #-1, [t]
+                    jnz [t], #loop
+                    in [y]
+                    add [addr], #1, [dest]
+                    add [y], #1, [y]
+                    out [dest]
+                    out [x]
+                    out [y]
+                    jz #0, #loop
+            addr:   data 0
+            dest:   data 0
+            x:      data 0
+            y:      data 0
+            t:      data 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        sent: Vec<Packet>,
+        idle: usize,
+        stop_at_y: isize,
+    }
+
+    impl Monitor for Recorder {
+        fn sent(&mut self, packet: &Packet, actions: &mut Actions) {
+            self.sent.push(*packet);
+            if packet.y == self.stop_at_y {
+                actions.stop();
+            }
+        }
+
+        fn idle(&mut self, actions: &mut Actions) {
+            self.idle += 1;
+            match self.idle {
+                1 => actions.inject(Packet {
+                    source: 255,
+                    dest: 0,
+                    x: 5,
+                    y: 20,
+                }),
+                _ => actions.stop(),
+            }
+        }
+    }
+
+    fn machines(n: usize) -> Vec<Machine> {
+        (0..n).map(|_| Machine::new(forwarder())).collect()
+    }
+
+    #[test]
+    fn chain_in_one_round() {
+        let mut network = PacketNetwork::boot(machines(4), Direct);
+        let mut monitor = Recorder {
+            stop_at_y: 3,
+            ..Recorder::default()
+        };
+        assert!(!network.round(&mut monitor).unwrap());
+        let hops: Vec<(isize, isize, isize)> = monitor
+            .sent
+            .iter()
+            .map(|p| (p.source, p.dest, p.y))
+            .collect();
+        assert_eq!(hops, vec![(0, 1, 0), (1, 2, 1), (2, 3, 2), (3, 4, 3)]);
+    }
+
+    #[test]
+    fn idle_injection() {
+        let mut network = PacketNetwork::boot(machines(2), Direct);
+        let mut monitor = Recorder {
+            stop_at_y: -1,
+            ..Recorder::default()
+        };
+        network.run(&mut monitor).unwrap();
+        let ys: Vec<isize> = monitor.sent.iter().map(|p| p.y).collect();
+        assert_eq!(ys, vec![0, 1, 21, 22]);
+        assert_eq!(monitor.idle, 2);
+    }
+
+    #[test]
+    fn custom_router() {
+        // Wrap around into a ring
+        let ring = |packet: &Packet, machines: usize| {
+            Some(packet.dest.rem_euclid(machines as isize) as usize)
+        };
+        let mut network = PacketNetwork::boot(machines(3), ring);
+        let mut monitor = Recorder {
+            stop_at_y: 10,
+            ..Recorder::default()
+        };
+        network.run(&mut monitor).unwrap();
+        let last = monitor.sent.last().unwrap();
+        assert_eq!((last.source, last.dest, last.x), (1, 2, 7));
+        assert_eq!(network.queued(2).count(), 0);
+
+        // Routing past the last machine drops the packet
+        let mut network = PacketNetwork::boot(machines(2), |_: &Packet, _| Some(5));
+        network.run(&mut Recorder::default()).unwrap();
+    }
+
+    #[test]
+    fn turn_budget() {
+        // Sends forever without asking for input
+        let flood = assemble(
+            "
+                    in [100]
+            loop:   out #5
+                    out #1
+                    out #2
+                    jz #0, #loop
+            ",
+        )
+        .unwrap();
+        let mut network = PacketNetwork::boot(vec![Machine::new(flood)], Direct);
+        network.turn_budget = 42;
+        let mut monitor = Recorder::default();
+        assert!(network.round(&mut monitor).unwrap());
+        // The 11th packet is finished on the next turn
+        assert_eq!(monitor.sent.len(), 10);
+        assert!(network.round(&mut monitor).unwrap());
+        assert_eq!(monitor.sent.len(), 21);
+        assert_eq!(monitor.idle, 0);
+
+        // Runs an instruction a turn instead
+        network.turn_budget = 0;
+        for _ in 0..4 {
+            assert!(network.round(&mut monitor).unwrap());
+        }
+        assert_eq!(monitor.sent.len(), 22);
+    }
+
+    #[test]
+    fn partial_packet() {
+        let half = assemble("in [0]\nout #1\nhalt").unwrap();
+        let mut network = PacketNetwork::boot(vec![Machine::new(half)], Direct);
+        assert_eq!(
+            network.run(&mut ()),
+            Err(IntcodeError::UnexpectedState {
+                pc: 4,
+                raw: 99,
+                state: State::Halted
+            })
+        );
+    }
+}
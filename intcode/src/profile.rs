@@ -0,0 +1,148 @@
+// Execution counts collected by a machine with profiling turned on (see
+// `Machine::set_profiling`).
+
+use crate::disasm::decode;
+use crate::instruction::{op_info, Opcode};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::hash::Hash;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    // Instructions executed, by pc
+    pub instructions: HashMap<usize, u64>,
+    pub opcodes: HashMap<Opcode, u64>,
+    // Parameters read from and results written to memory, by address
+    pub reads: HashMap<usize, u64>,
+    pub writes: HashMap<usize, u64>,
+    // Backward jumps taken, by the (target, pc of the jump) range they loop
+    // over
+    pub loops: HashMap<(usize, usize), u64>,
+}
+
+fn count<K: Hash + Eq>(counts: &mut HashMap<K, u64>, key: K) {
+    *counts.entry(key).or_insert(0) += 1;
+}
+
+// Most counted first, ties broken by key
+fn top<K: Copy + Ord>(counts: &HashMap<K, u64>, n: usize) -> Vec<(K, u64)> {
+    let mut top: Vec<(K, u64)> = counts.iter().map(|(&k, &c)| (k, c)).collect();
+    top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    top.truncate(n);
+    top
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile::default()
+    }
+
+    pub(crate) fn instruction(&mut self, pc: usize, opcode: Opcode) {
+        count(&mut self.instructions, pc);
+        count(&mut self.opcodes, opcode);
+    }
+
+    pub(crate) fn read(&mut self, address: usize) {
+        count(&mut self.reads, address);
+    }
+
+    pub(crate) fn write(&mut self, address: usize) {
+        count(&mut self.writes, address);
+    }
+
+    pub(crate) fn jump(&mut self, pc: usize, target: usize) {
+        if target <= pc {
+            count(&mut self.loops, (target, pc));
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.instructions.values().sum()
+    }
+
+    // The `n` hottest instructions, loops, opcodes and addresses. `load`
+    // reads the program so instructions can be disassembled.
+    pub fn report(&self, load: impl Fn(usize) -> isize, n: usize) -> String {
+        let mut s = String::new();
+        let total = self.total();
+        let percent = |c: u64| 100.0 * c as f64 / total.max(1) as f64;
+        writeln!(s, "{} instructions executed", total).unwrap();
+
+        writeln!(s, "\nhottest instructions:").unwrap();
+        for (pc, c) in top(&self.instructions, n) {
+            let text = decode(&load, pc).map_or("?".to_string(), |inst| inst.to_string());
+            writeln!(s, "{:>12} {:>5.1}% {:>6}: {}", c, percent(c), pc, text).unwrap();
+        }
+
+        writeln!(s, "\nhottest loops:").unwrap();
+        for ((start, end), c) in top(&self.loops, n) {
+            let executed: u64 = (start..=end)
+                .filter_map(|pc| self.instructions.get(&pc))
+                .sum();
+            writeln!(
+                s,
+                "{:>12} iterations of {}..={}, {:.1}% of instructions",
+                c,
+                start,
+                end,
+                percent(executed)
+            )
+            .unwrap();
+        }
+
+        writeln!(s, "\nopcodes:").unwrap();
+        for (opcode, c) in top(&self.opcodes, n) {
+            let mnemonic = op_info(opcode).map_or("?", |info| info.mnemonic);
+            writeln!(s, "{:>12} {:>5.1}% {}", c, percent(c), mnemonic).unwrap();
+        }
+
+        for (name, counts) in &[("reads", &self.reads), ("writes", &self.writes)] {
+            writeln!(s, "\nmost {}:", name).unwrap();
+            for (address, c) in top(counts, n) {
+                writeln!(s, "{:>12} [{}]", c, address).unwrap();
+            }
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::{Machine, Memory, State};
+
+    #[test]
+    fn counts() {
+        let prog = assemble(
+            "
+            loop:   add [n], #-1, [n]
+                    jnz [n], #loop
+                    out [n]
+                    halt
+            n:      data 3
+            ",
+        )
+        .unwrap();
+        let mut m = Machine::new(prog);
+        assert!(m.profile().is_none());
+        m.set_profiling(true);
+        assert_eq!(m.resume().unwrap(), State::Output(0));
+        assert_eq!(m.resume().unwrap(), State::Halted);
+
+        let profile = m.profile().unwrap();
+        assert_eq!(profile.total(), 8);
+        assert_eq!(top(&profile.instructions, 2), vec![(0, 3), (4, 3)]);
+        assert_eq!(profile.opcodes[&1], 3);
+        assert_eq!(profile.opcodes[&99], 1);
+        assert_eq!(profile.reads[&10], 4 + 3);
+        assert_eq!(profile.writes[&10], 3);
+        assert_eq!(profile.loops, vec![((0, 4), 2)].into_iter().collect());
+
+        let report = profile.report(|a| m.memory().load(a), 1);
+        assert!(report.starts_with("8 instructions executed\n"));
+        assert!(report.contains("           3  37.5%      0: add [10], #-1, [10]\n"));
+        assert!(report.contains("           2 iterations of 0..=4, 75.0% of instructions\n"));
+        assert!(report.contains("           3  37.5% add\n"));
+    }
+}
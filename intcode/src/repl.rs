@@ -0,0 +1,252 @@
+// An interactive session on a program: run, step, poke and peek at memory,
+// and patch the program so changes survive resets. Shared with the debugger
+// are `parse` for command arguments and `read_loop` for the prompt.
+
+use crate::limits::{Limits, Outcome};
+use crate::{Machine, PagedMemory, Prog, State, VecIO};
+use std::fmt::Write as _;
+use std::io::{stdin, stdout, BufRead, Write};
+use std::ops::Range;
+
+pub const HELP: &str = "\
+commands:
+  run [n]              run until halt or input is needed (at most n instructions)
+  step [n]             run n instructions (default 1)
+  poke <addr> <v>      write to memory
+  peek <addr>[..<len>] show len words (default 1) starting at addr
+  input <v,v,...>      queue inputs
+  outputs              show every output since the last reset
+  patch <addr> <v>     write to memory now and after every reset
+  patches              list patches
+  reset                reload the program (with patches)
+  q                    quit";
+
+pub fn parse<T: std::str::FromStr>(arg: Option<&str>) -> Result<T, String> {
+    let arg = arg.ok_or_else(|| "missing argument".to_string())?;
+    arg.parse().map_err(|_| format!("invalid number `{}`", arg))
+}
+
+// Commands show at most this many words of memory at a time
+const MAX_SHOWN: usize = 10_000;
+
+// The `len` addresses from `address`, for commands showing memory
+pub fn address_range(address: usize, len: usize) -> Result<Range<usize>, String> {
+    if len > MAX_SHOWN {
+        return Err(format!("can show at most {} words", MAX_SHOWN));
+    }
+    let end = address
+        .checked_add(len)
+        .ok_or_else(|| "past the end of memory".to_string())?;
+    Ok(address..end)
+}
+
+// Prompts for commands on stdin until `command` returns false or stdin ends.
+pub fn read_loop(prompt: &str, mut command: impl FnMut(&str) -> Result<bool, String>) {
+    loop {
+        print!("{}", prompt);
+        stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin().lock().read_line(&mut line).unwrap() == 0 {
+            return;
+        }
+        match command(&line) {
+            Ok(true) => (),
+            Ok(false) => return,
+            Err(e) => println!("error: {}", e),
+        }
+    }
+}
+
+pub struct Repl {
+    prog: Prog,
+    patches: Vec<(usize, isize)>,
+    pub machine: Machine<PagedMemory>,
+    pub outputs: Vec<isize>,
+}
+
+impl Repl {
+    pub fn new(prog: Prog) -> Repl {
+        Repl {
+            machine: Machine::new(PagedMemory::from(&prog)),
+            prog,
+            patches: vec![],
+            outputs: vec![],
+        }
+    }
+
+    pub fn patches(&self) -> &[(usize, isize)] {
+        &self.patches
+    }
+
+    pub fn reset(&mut self) {
+        self.machine = Machine::new(PagedMemory::from(&self.prog));
+        for &(address, value) in &self.patches {
+            self.machine.store(address, value);
+        }
+        self.outputs.clear();
+    }
+
+    // Runs one command, writing what it shows to `out`. Returns false on quit.
+    pub fn command(&mut self, line: &str, out: &mut String) -> Result<bool, String> {
+        let mut words = line.split_whitespace();
+        let outputs_before = self.outputs.len();
+        match words.next() {
+            None => (),
+            Some("run") => {
+                let limits = Limits {
+                    max_instructions: words.next().map(|n| parse(Some(n))).transpose()?,
+                    ..Limits::default()
+                };
+                let mut io = VecIO::default();
+                let outcome = self.machine.run_limited(&mut io, &limits);
+                self.outputs.extend(io.outputs);
+                match outcome {
+                    Ok(Outcome::NeedsInput) => {
+                        writeln!(out, "needs input at pc {}", self.machine.pc).unwrap()
+                    }
+                    Ok(Outcome::Halted) => writeln!(out, "halted").unwrap(),
+                    Ok(_) => writeln!(out, "stopped at pc {}", self.machine.pc).unwrap(),
+                    Err(e) => writeln!(out, "error: {}", e).unwrap(),
+                }
+            }
+            Some("step") => {
+                let n = words.next().map_or(Ok(1), |n| parse(Some(n)))?;
+                for _ in 0..n {
+                    match self.machine.step() {
+                        Ok(None) => (),
+                        Ok(Some(State::Output(o))) => self.outputs.push(o),
+                        Ok(Some(state)) => {
+                            writeln!(out, "{:?}", state).unwrap();
+                            break;
+                        }
+                        Err(e) => {
+                            writeln!(out, "error: {}", e).unwrap();
+                            break;
+                        }
+                    }
+                }
+                writeln!(out, "pc {}", self.machine.pc).unwrap();
+            }
+            Some("poke") => {
+                let address = parse(words.next())?;
+                self.machine.store(address, parse(words.next())?);
+            }
+            Some("peek") => {
+                let arg = words.next().unwrap_or("");
+                let mut parts = arg.splitn(2, "..");
+                let address: usize = parse(parts.next())?;
+                let len = parts.next().map_or(Ok(1), |n| parse(Some(n)))?;
+                for a in address_range(address, len)? {
+                    writeln!(out, "[{}] {}", a, self.machine.load(a)).unwrap();
+                }
+            }
+            Some("input") => {
+                for v in words.collect::<String>().split(',') {
+                    self.machine.push_input(parse(Some(v.trim()))?);
+                }
+            }
+            Some("outputs") => writeln!(out, "{:?}", self.outputs).unwrap(),
+            Some("patch") => {
+                let address = parse(words.next())?;
+                let value = parse(words.next())?;
+                self.patches.push((address, value));
+                self.machine.store(address, value);
+            }
+            Some("patches") => {
+                for (address, value) in &self.patches {
+                    writeln!(out, "[{}] {}", address, value).unwrap();
+                }
+            }
+            Some("reset") => self.reset(),
+            Some("q") => return Ok(false),
+            Some(_) => writeln!(out, "{}", HELP).unwrap(),
+        };
+        for o in self.outputs.iter().skip(outputs_before) {
+            writeln!(out, " > {}", o).unwrap();
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    // Runs each command, returning everything they showed
+    fn commands(repl: &mut Repl, lines: &[&str]) -> String {
+        let mut out = String::new();
+        for line in lines {
+            assert_eq!(repl.command(line, &mut out), Ok(true));
+        }
+        out
+    }
+
+    // Outputs [x] + each input, until it's given a 0
+    fn repl() -> Repl {
+        let prog = assemble(
+            "
+            loop:   in [i]
+                    jz [i], #done
+                    add [x], [i], [i]
+                    out [i]
+                    jz #0, #loop
+            done:   halt
+            i:      data 0
+            x:      data 10
+            ",
+        );
+        Repl::new(prog.unwrap())
+    }
+
+    #[test]
+    fn peek() {
+        let mut repl = repl();
+        assert_eq!(commands(&mut repl, &["peek 15..2"]), "[15] 0\n[16] 10\n");
+        assert_eq!(commands(&mut repl, &["peek 16"]), "[16] 10\n");
+        let mut out = String::new();
+        assert!(repl.command("peek x", &mut out).is_err());
+        assert!(repl.command("peek 15..", &mut out).is_err());
+        assert_eq!(
+            repl.command("peek 18446744073709551615..2", &mut out),
+            Err("past the end of memory".to_string())
+        );
+        assert_eq!(
+            repl.command("peek 0..1000000000", &mut out),
+            Err("can show at most 10000 words".to_string())
+        );
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn run_stops_for_input() {
+        let mut repl = repl();
+        assert_eq!(
+            commands(&mut repl, &["run", "input 1,2", "run"]),
+            "needs input at pc 0\nneeds input at pc 0\n > 11\n > 12\n"
+        );
+        assert_eq!(
+            commands(&mut repl, &["input 0", "run 1"]),
+            "stopped at pc 2\n"
+        );
+        assert_eq!(commands(&mut repl, &["run"]), "halted\n");
+        assert_eq!(repl.outputs, vec![11, 12]);
+    }
+
+    #[test]
+    fn patches_survive_reset() {
+        let mut repl = repl();
+        commands(&mut repl, &["patch 16 100", "poke 15 7", "input 1", "run"]);
+        assert_eq!(repl.outputs, vec![101]);
+        commands(&mut repl, &["reset"]);
+        assert!(repl.outputs.is_empty());
+        assert_eq!(repl.machine.load(16), 100);
+        assert_eq!(repl.machine.load(15), 0);
+        assert_eq!(repl.patches(), &[(16, 100)]);
+        assert_eq!(
+            commands(&mut repl, &["input 2", "run"]),
+            "needs input at pc 0\n > 102\n"
+        );
+        assert_eq!(repl.command("q", &mut String::new()), Ok(false));
+    }
+}
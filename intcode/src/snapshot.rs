@@ -0,0 +1,220 @@
+// Saves and restores a machine's full state (memory, pc, relative base,
+// queued inputs and settings) as text:
+//
+//     intcode-snapshot 1
+//     pc 4
+//     base 0
+//     inputs 1,2
+//     checked_arithmetic true
+//     decode_cache true
+//     memory 0 1102,34915192,34915192,7
+//     memory 7 1219070632396864
+//
+// Each `memory` line is a run of words starting at an address. Words not
+// listed are 0. Settings not listed are left at their defaults.
+
+use crate::{Machine, Memory};
+use std::fs::{read_to_string, write};
+use std::io;
+use std::path::Path;
+
+const HEADER: &str = "intcode-snapshot 1";
+// Runs of zeros shorter than this are written out rather than starting a new
+// `memory` line.
+const MAX_ZEROS_IN_RUN: usize = 8;
+
+fn join(words: &[isize]) -> String {
+    words
+        .iter()
+        .map(isize::to_string)
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn invalid(line: usize, message: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("snapshot line {}: {}", line, message),
+    )
+}
+
+fn parse_words(line: usize, s: &str) -> io::Result<Vec<isize>> {
+    if s.is_empty() {
+        return Ok(vec![]);
+    }
+    s.split(',')
+        .map(|w| {
+            w.parse()
+                .map_err(|_| invalid(line, format!("invalid number `{}`", w)))
+        })
+        .collect()
+}
+
+impl<M: Memory> Machine<M> {
+    pub fn to_snapshot(&self) -> String {
+        let mut s = format!(
+            "{}\npc {}\nbase {}\ninputs {}\nchecked_arithmetic {}\ndecode_cache {}\n",
+            HEADER,
+            self.pc,
+            self.base,
+            join(&self.pending_inputs().cloned().collect::<Vec<isize>>()),
+            self.checked_arithmetic(),
+            self.decode_cache_enabled()
+        );
+        let mut run: Option<(usize, Vec<isize>)> = None;
+        for (address, value) in self.memory().words() {
+            if let Some((start, words)) = &mut run {
+                let end = *start + words.len();
+                if address - end < MAX_ZEROS_IN_RUN {
+                    words.resize(address - *start, 0);
+                    words.push(value);
+                    continue;
+                }
+                s += &format!("memory {} {}\n", start, join(words));
+            }
+            run = Some((address, vec![value]));
+        }
+        if let Some((start, words)) = run {
+            s += &format!("memory {} {}\n", start, join(&words));
+        }
+        s
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        write(path, self.to_snapshot())
+    }
+}
+
+impl<M: Memory + Default> Machine<M> {
+    pub fn from_snapshot(snapshot: &str) -> io::Result<Machine<M>> {
+        let mut lines = snapshot.lines().enumerate().map(|(i, l)| (i + 1, l.trim()));
+        match lines.next() {
+            Some((_, HEADER)) => (),
+            _ => return Err(invalid(1, format!("expected `{}`", HEADER))),
+        }
+        let mut machine = Machine::new(M::default());
+        let mut decode_cache = true;
+        for (line, s) in lines.filter(|(_, s)| !s.is_empty()) {
+            let mut parts = s.splitn(2, ' ');
+            let key = parts.next().unwrap();
+            let rest = parts.next().unwrap_or("").trim();
+            let number = |s: &str| {
+                s.parse()
+                    .map_err(|_| invalid(line, format!("invalid number `{}`", s)))
+            };
+            let setting = |s: &str| {
+                s.parse()
+                    .map_err(|_| invalid(line, format!("expected true or false, not `{}`", s)))
+            };
+            match key {
+                "pc" => machine.pc = number(rest)?,
+                "base" => machine.base = number(rest)?,
+                "checked_arithmetic" => machine.set_checked_arithmetic(setting(rest)?),
+                // Set once memory is restored, so the cache is sized to it
+                "decode_cache" => decode_cache = setting(rest)?,
+                "inputs" => {
+                    for input in parse_words(line, rest)? {
+                        machine.push_input(input);
+                    }
+                }
+                "memory" => {
+                    let mut parts = rest.splitn(2, ' ');
+                    let start: usize = number(parts.next().unwrap())?;
+                    let words = parse_words(line, parts.next().unwrap_or("").trim())?;
+                    for (i, word) in words.into_iter().enumerate() {
+                        let address = start
+                            .checked_add(i)
+                            .ok_or_else(|| invalid(line, "memory past the last address".into()))?;
+                        machine.store(address, word);
+                    }
+                }
+                _ => return Err(invalid(line, format!("unknown entry `{}`", key))),
+            }
+        }
+        machine.set_decode_cache(decode_cache);
+        Ok(machine)
+    }
+
+    pub fn restore(path: impl AsRef<Path>) -> io::Result<Machine<M>> {
+        Machine::from_snapshot(&read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{read_prog, PagedMemory, Prog, State};
+
+    #[test]
+    fn round_trip() {
+        let mut m = Machine::new(read_prog("109,5,3,0,3,20,4,20,99"));
+        m.push_input(7);
+        m.push_input(-8);
+        m.push_input(9);
+        m.step().unwrap();
+        m.step().unwrap();
+        assert_eq!(
+            m.to_snapshot(),
+            "intcode-snapshot 1\n\
+             pc 4\n\
+             base 5\n\
+             inputs -8,9\n\
+             checked_arithmetic true\n\
+             decode_cache true\n\
+             memory 0 7,5,3,0,3,20,4,20,99\n"
+        );
+
+        let path = std::env::temp_dir().join("intcode-snapshot-round-trip.txt");
+        m.save(&path).unwrap();
+        let mut restored: Machine<PagedMemory> = Machine::restore(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(restored.pc, 4);
+        assert_eq!(restored.base, 5);
+        assert_eq!(restored.resume().unwrap(), State::Output(-8));
+        assert_eq!(restored.to_snapshot().lines().nth(1), Some("pc 8"));
+
+        m.set_checked_arithmetic(false);
+        m.set_decode_cache(false);
+        let restored: Machine = Machine::from_snapshot(&m.to_snapshot()).unwrap();
+        assert!(!restored.checked_arithmetic());
+        assert!(!restored.decode_cache_enabled());
+    }
+
+    #[test]
+    fn memory_runs() {
+        let mut m = Machine::new(Prog::new());
+        m.store(3, 1);
+        m.store(10, 2);
+        m.store(100, 3);
+        let snapshot = m.to_snapshot();
+        assert!(snapshot.ends_with("memory 3 1,0,0,0,0,0,0,2\nmemory 100 3\n"));
+        let restored: Machine = Machine::from_snapshot(&snapshot).unwrap();
+        assert_eq!(restored.memory().words(), m.memory().words());
+    }
+
+    #[test]
+    fn errors() {
+        let e = |s: &str| Machine::<Prog>::from_snapshot(s).err().unwrap().to_string();
+        assert_eq!(e("pc 1"), "snapshot line 1: expected `intcode-snapshot 1`");
+        assert_eq!(
+            e("intcode-snapshot 1\npc x"),
+            "snapshot line 2: invalid number `x`"
+        );
+        assert_eq!(
+            e("intcode-snapshot 1\nmemory 0 1,,2"),
+            "snapshot line 2: invalid number ``"
+        );
+        assert_eq!(
+            e("intcode-snapshot 1\nregisters"),
+            "snapshot line 2: unknown entry `registers`"
+        );
+        assert_eq!(
+            e("intcode-snapshot 1\nmemory 18446744073709551615 1,2"),
+            "snapshot line 2: memory past the last address"
+        );
+        assert_eq!(
+            e("intcode-snapshot 1\ndecode_cache yes"),
+            "snapshot line 2: expected true or false, not `yes`"
+        );
+    }
+}
@@ -0,0 +1,424 @@
+// Converts a program to a Rust module, for programs hot enough that
+// interpreting them is too slow.
+//
+// Each basic block (see `cfg`) becomes straight-line Rust in one arm of a
+// dispatch loop on the pc, so nothing is decoded at run time. The generated
+// module has
+//
+//     pub fn run(io: &mut impl InputOutput) -> Result<(), IntcodeError>
+//
+// which behaves exactly like `Machine::run` on the original program. Anything
+// the generated code can't handle (a write into the program's code, a jump to
+// somewhere that wasn't transpiled, or anything that would fail) hands the
+// machine's state to the interpreter to carry on from that instruction.
+
+use crate::cfg::{control_flow_graph_from, Cfg};
+use crate::disasm::{Instruction, Line, Param};
+use crate::instruction::ParamMode;
+use crate::{InputOutput, IntcodeError, Machine, Prog};
+use std::collections::BTreeSet;
+
+// Writes beyond this aren't worth growing the generated code's memory for
+const MAX_NATIVE_ADDRESS: usize = 1 << 20;
+
+// State for the generated code, which reports anything it can't do by
+// returning the pc to fall back to the interpreter at (the `Err(usize)`s).
+#[derive(Debug)]
+pub struct Runtime {
+    memory: Vec<isize>,
+    // Whether each word is part of a transpiled instruction
+    code: Vec<bool>,
+    base: usize,
+}
+
+impl Runtime {
+    pub fn new(memory: &[isize], code: &[(usize, usize)]) -> Runtime {
+        let mut is_code = vec![false; code.iter().map(|&(_, end)| end).max().unwrap_or(0)];
+        for &(start, end) in code {
+            for word in &mut is_code[start..end] {
+                *word = true;
+            }
+        }
+        Runtime {
+            memory: memory.to_vec(),
+            code: is_code,
+            base: 0,
+        }
+    }
+
+    pub fn load(&self, address: usize) -> isize {
+        *self.memory.get(address).unwrap_or(&0)
+    }
+
+    pub fn store(&mut self, address: usize, value: isize) {
+        if address >= self.memory.len() {
+            self.memory.resize(address + 1, 0);
+        }
+        self.memory[address] = value;
+    }
+
+    pub fn relative(&self, offset: isize, pc: usize) -> Result<usize, usize> {
+        match (self.base as isize).checked_add(offset) {
+            Some(address) if address >= 0 => Ok(address as usize),
+            _ => Err(pc),
+        }
+    }
+
+    // `address`, if writing it can't change any transpiled code
+    pub fn writable(&self, address: usize, pc: usize) -> Result<usize, usize> {
+        if address >= MAX_NATIVE_ADDRESS || self.code.get(address) == Some(&true) {
+            Err(pc)
+        } else {
+            Ok(address)
+        }
+    }
+
+    pub fn adjust_base(&mut self, delta: isize, pc: usize) -> Result<(), usize> {
+        self.base = self.relative(delta, pc)?;
+        Ok(())
+    }
+
+    pub fn add(&self, a: isize, b: isize, pc: usize) -> Result<isize, usize> {
+        a.checked_add(b).ok_or(pc)
+    }
+
+    pub fn mul(&self, a: isize, b: isize, pc: usize) -> Result<isize, usize> {
+        a.checked_mul(b).ok_or(pc)
+    }
+
+    pub fn input(&self, io: &mut impl InputOutput, pc: usize) -> Result<isize, usize> {
+        io.input().ok_or(pc)
+    }
+
+    pub fn target(&self, value: isize, pc: usize) -> Result<usize, usize> {
+        if value < 0 {
+            Err(pc)
+        } else {
+            Ok(value as usize)
+        }
+    }
+
+    // Runs the rest of the program in the interpreter, starting at `pc`
+    pub fn fall_back(self, pc: usize, io: &mut impl InputOutput) -> Result<(), IntcodeError> {
+        let prog: Prog = self
+            .memory
+            .iter()
+            .enumerate()
+            .filter(|(_, &word)| word != 0)
+            .map(|(address, &word)| (address, word))
+            .collect();
+        let mut machine = Machine::new(prog);
+        machine.pc = pc;
+        machine.base = self.base;
+        machine.run(io)
+    }
+}
+
+// Calls push a return address (ex. `add #ret, #0, [rb+1]`) and then jump, so
+// the code after the jump is only reached by an indirect jump back.
+fn return_points(prog: &Prog, cfg: &Cfg) -> Vec<usize> {
+    let constants: BTreeSet<isize> = instructions(cfg)
+        .filter(|inst| inst.params.iter().take(2).all(is_immediate))
+        .filter_map(|inst| match inst.opcode() {
+            1 => inst.params[0].raw.checked_add(inst.params[1].raw),
+            2 => inst.params[0].raw.checked_mul(inst.params[1].raw),
+            _ => None,
+        })
+        .collect();
+    instructions(cfg)
+        .filter(|inst| matches!(inst.opcode(), 5 | 6))
+        .map(|inst| inst.address + inst.size())
+        .filter(|&after| constants.contains(&(after as isize)))
+        .filter(|after| !cfg.blocks.contains_key(after) && prog.contains_key(after))
+        .collect()
+}
+
+fn instructions(cfg: &Cfg) -> impl Iterator<Item = &Instruction> {
+    cfg.blocks
+        .values()
+        .flat_map(|block| &block.lines)
+        .filter_map(|line| match line {
+            Line::Instruction(inst) => Some(inst),
+            Line::Data { .. } => None,
+        })
+}
+
+fn is_immediate(param: &Param) -> bool {
+    param.mode == ParamMode::Immediate
+}
+
+// The code reachable from address 0 or any return point
+fn code(prog: &Prog) -> Cfg {
+    let mut entries = vec![0];
+    loop {
+        let cfg = control_flow_graph_from(prog, &entries);
+        let found = return_points(prog, &cfg);
+        if found.is_empty() {
+            return cfg;
+        }
+        entries.extend(found);
+    }
+}
+
+// Rust for a value param, or `None` if it always faults
+fn value(param: &Param, pc: usize) -> Option<String> {
+    match param.mode {
+        ParamMode::Immediate => Some(param.raw.to_string()),
+        ParamMode::Position if param.raw < 0 => None,
+        ParamMode::Position => Some(format!("rt.load({})", param.raw)),
+        _ => Some(format!("rt.load(rt.relative({}, {})?)", param.raw, pc)),
+    }
+}
+
+// Rust for an address param, or `None` if writing it always falls back
+fn address(param: &Param, pc: usize, code: &BTreeSet<usize>) -> Option<String> {
+    match param.mode {
+        ParamMode::RelativeToBaseAddress => Some(format!(
+            "rt.writable(rt.relative({}, {})?, {})?",
+            param.raw, pc, pc
+        )),
+        _ if param.raw < 0
+            || param.raw as usize >= MAX_NATIVE_ADDRESS
+            || code.contains(&(param.raw as usize)) =>
+        {
+            None
+        }
+        _ => Some(param.raw.to_string()),
+    }
+}
+
+// A statement for an instruction (if it does more than jump), and the pc to
+// carry on at. `None` if it always falls back.
+fn instruction(inst: &Instruction, code: &BTreeSet<usize>) -> Option<(Option<String>, String)> {
+    let pc = inst.address;
+    let v = |i: usize| value(&inst.params[i], pc);
+    let a = |i: usize| address(&inst.params[i], pc, code);
+    let next = (inst.address + inst.size()).to_string();
+    let stmt = match inst.opcode() {
+        // Worked out now if both operands are immediate
+        1 | 2 | 7 | 8 if inst.params.iter().take(2).all(is_immediate) => {
+            let (x, y) = (inst.params[0].raw, inst.params[1].raw);
+            let result = match inst.opcode() {
+                1 => x.checked_add(y)?,
+                2 => x.checked_mul(y)?,
+                7 => (x < y) as isize,
+                _ => (x == y) as isize,
+            };
+            format!("rt.store({}, {});", a(2)?, result)
+        }
+        1 => format!(
+            "rt.store({}, rt.add({}, {}, {})?);",
+            a(2)?,
+            v(0)?,
+            v(1)?,
+            pc
+        ),
+        2 => format!(
+            "rt.store({}, rt.mul({}, {}, {})?);",
+            a(2)?,
+            v(0)?,
+            v(1)?,
+            pc
+        ),
+        3 => format!("rt.store({}, rt.input(io, {})?);", a(0)?, pc),
+        4 => format!("io.output({});", v(0)?),
+        5 | 6 => {
+            let (condition, target) = (&inst.params[0], &inst.params[1]);
+            let jump = match target.mode {
+                ParamMode::Immediate if target.raw < 0 => format!("return Err({})", pc),
+                ParamMode::Immediate => target.raw.to_string(),
+                _ => format!("rt.target({}, {})?", v(1)?, pc),
+            };
+            let jump_if_zero = inst.opcode() == 6;
+            if is_immediate(condition) {
+                let jumps = (condition.raw == 0) == jump_if_zero;
+                return Some((None, if jumps { jump } else { next }));
+            }
+            let test = if jump_if_zero { "==" } else { "!=" };
+            let branch = format!("if {} {} 0 {{ {} }} else {{ {} }}", v(0)?, test, jump, next);
+            return Some((None, branch));
+        }
+        7 => format!("rt.store({}, ({} < {}) as isize);", a(2)?, v(0)?, v(1)?),
+        8 => format!("rt.store({}, ({} == {}) as isize);", a(2)?, v(0)?, v(1)?),
+        9 => format!("rt.adjust_base({}, {})?;", v(0)?, pc),
+        _ => return Some((None, "return Ok(())".to_string())),
+    };
+    Some((Some(stmt), next))
+}
+
+// Source for a module running `prog` (see the top of this file)
+pub fn transpile(prog: &Prog) -> String {
+    let cfg = code(prog);
+    let mut code = BTreeSet::new();
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for inst in instructions(&cfg) {
+        let (start, end) = (inst.address, inst.address + inst.size());
+        code.extend(start..end);
+        match ranges.last_mut() {
+            Some(last) if last.1 >= start => last.1 = last.1.max(end),
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    let len = prog.keys().max().map_or(0, |&max| max + 1);
+    let memory: Vec<String> = (0..len)
+        .map(|address| prog.get(&address).unwrap_or(&0).to_string())
+        .collect();
+    let ranges: Vec<String> = ranges
+        .iter()
+        .map(|(start, end)| format!("({}, {})", start, end))
+        .collect();
+
+    let mut src = String::new();
+    src += "// Generated by intcode::transpile\n\n";
+    src += "use intcode::transpile::Runtime;\n";
+    src += "use intcode::{InputOutput, IntcodeError};\n\n";
+    src += &format!(
+        "static MEMORY: [isize; {}] = [{}];\n\n",
+        memory.len(),
+        memory.join(", ")
+    );
+    src += "// Words of transpiled instructions\n";
+    src += &format!(
+        "static CODE: [(usize, usize); {}] = [{}];\n\n",
+        ranges.len(),
+        ranges.join(", ")
+    );
+    src += "pub fn run(io: &mut impl InputOutput) -> Result<(), IntcodeError> {\n";
+    src += "    let mut rt = Runtime::new(&MEMORY, &CODE);\n";
+    src += "    match execute(&mut rt, io) {\n";
+    src += "        Ok(()) => Ok(()),\n";
+    src += "        Err(pc) => rt.fall_back(pc, io),\n";
+    src += "    }\n";
+    src += "}\n\n";
+    src += "// Every arm returns in programs without jumps\n";
+    src += "#[allow(unreachable_code, unused_mut, clippy::never_loop)]\n";
+    src += "fn execute(rt: &mut Runtime, io: &mut impl InputOutput) -> Result<(), usize> {\n";
+    src += "    let mut pc = 0;\n";
+    src += "    loop {\n";
+    src += "        pc = match pc {\n";
+    for block in cfg.blocks.values() {
+        src += &format!("            {} => {{\n", block.start);
+        let mut next = String::new();
+        for line in &block.lines {
+            let inst = match line {
+                Line::Instruction(inst) => inst,
+                Line::Data { address, .. } => {
+                    next = format!("return Err({})", address);
+                    break;
+                }
+            };
+            src += &format!("                // {}: {}\n", inst.address, inst);
+            match instruction(inst, &code) {
+                Some((stmt, after)) => {
+                    if let Some(stmt) = stmt {
+                        src += &format!("                {}\n", stmt);
+                    }
+                    next = after;
+                }
+                None => {
+                    next = format!("return Err({})", inst.address);
+                    break;
+                }
+            }
+        }
+        src += &format!("                {}\n", next);
+        src += "            }\n";
+    }
+    src += "            _ => return Err(pc),\n";
+    src += "        };\n";
+    src += "    }\n";
+    src += "}\n";
+    src
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::compiler::compile_prog;
+    use crate::{read_prog, VecIO};
+
+    #[test]
+    fn finds_return_points() {
+        let prog = compile_prog(
+            "
+            fn double(x) { return x * 2; }
+            fn main() { write(double(double(read()))); }
+            ",
+        )
+        .unwrap();
+        let cfg = code(&prog);
+        // Every jump lands on a transpiled block
+        let mut machine = Machine::new(prog.clone());
+        machine.push_input(5);
+        loop {
+            let pc = machine.pc;
+            let op = machine.load(pc) % 100;
+            if machine.step().unwrap().is_some() {
+                break;
+            }
+            if op == 5 || op == 6 {
+                assert!(cfg.blocks.contains_key(&machine.pc), "{}", machine.pc);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_source() {
+        let prog = assemble(
+            "
+                    in [rb+1]
+                    jz [rb+1], #done
+                    add [rb+1], #-1, [x]
+                    add #1, #0, [1]
+            done:   out [x]
+                    halt
+            x:      data 0
+            ",
+        )
+        .unwrap();
+        let src = transpile(&prog);
+        assert!(src.contains("static CODE: [(usize, usize); 1] = [(0, 16)];"));
+        let execute = &src[src.find("fn execute").unwrap()..];
+        assert_eq!(
+            execute.lines().skip(4).take(16).collect::<Vec<&str>>(),
+            vec![
+                "            0 => {",
+                "                // 0: in [rb+1]",
+                "                rt.store(rt.writable(rt.relative(1, 0)?, 0)?, rt.input(io, 0)?);",
+                "                // 2: jz [rb+1], #13",
+                "                if rt.load(rt.relative(1, 2)?) == 0 { 13 } else { 5 }",
+                "            }",
+                "            5 => {",
+                "                // 5: add [rb+1], #-1, [16]",
+                "                rt.store(16, rt.add(rt.load(rt.relative(1, 5)?), -1, 5)?);",
+                "                // 9: add #1, #0, [1]",
+                "                return Err(9)",
+                "            }",
+                "            13 => {",
+                "                // 13: out [16]",
+                "                io.output(rt.load(16));",
+                "                // 15: halt",
+            ]
+        );
+    }
+
+    #[test]
+    fn falls_back() {
+        let mut rt = Runtime::new(&[4, 0, 99], &[(0, 3)]);
+        assert_eq!(rt.writable(0, 7), Err(7));
+        assert_eq!(rt.writable(3, 7), Ok(3));
+        // Carries on with the generated code's memory and base
+        rt.store(0, 204);
+        rt.adjust_base(2, 0).unwrap();
+        let mut io = VecIO::default();
+        rt.fall_back(0, &mut io).unwrap();
+        assert_eq!(io.outputs, vec![99]);
+
+        // Too far out to store natively
+        let src = transpile(&read_prog("1101,1,1,4000000000000,99"));
+        assert!(!src.contains("rt.store(4000000000000"));
+        assert!(src.contains("return Err(0)"));
+    }
+}